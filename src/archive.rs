@@ -0,0 +1,327 @@
+//! Length-prefixed framing of file handles and the archive and record formats built on it
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::ffi::OsString;
+use std::collections::TryReserveError;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::OsStringExt;
+use crate::HandleError;
+use crate::LinuxFileHandle;
+use crate::ffi_bindings::*;
+
+/// Largest frame accepted by ```read_from()```: two header words, the largest payload and one padding word
+const MAX_FRAME_LEN: u32 = 8 + MAX_HANDLE_SZ + 4;
+
+/// Largest path accepted by ```read_archive()```
+const MAX_PATH_LEN: u32 = 4096;
+
+/// Version of the record format written by ```to_described_record()```
+const DESCRIBED_RECORD_VERSION: u8 = 1;
+
+/// Flag of a described record that has a file-system UUID
+const DESCRIBED_RECORD_HAS_UUID: u8 = 1;
+
+/// A self-describing record parsed by ```LinuxFileHandle::from_described_record()```
+pub struct DescribedRecord
+{
+   /// The handle, with the mount ID it was obtained with (if any)
+   pub handle: LinuxFileHandle,
+   /// UUID of the file-system, if it could be resolved when the record was made
+   pub fs_uuid: Option<[u8; 16]>,
+   /// Path the handle was obtained from, resolved against the directory file descriptor if it was relative
+   pub path: PathBuf,
+}
+
+fn invalid_data(msg: &'static str) -> std::io::Error
+{
+   std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads a little-endian ```u32```, returning None if the reader is already at end-of-file
+fn read_u32_or_eof<R: Read>(r: &mut R) -> std::io::Result<Option<u32>>
+{
+   let mut buf: [u8; 4] = [0; 4];
+   let mut filled: usize = 0;
+   while filled < buf.len()
+   {
+      match r.read(&mut buf[filled..])
+      {
+         Ok(0) => break,
+         Ok(n) => filled += n,
+         Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+         Err(e) => return Err(e),
+      }
+   }
+   match filled
+   {
+      0 => Ok(None),
+      4 => Ok(Some(u32::from_le_bytes(buf))),
+      _ => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+   }
+}
+
+/// Turns an end-of-file inside a frame into ```Truncated```
+fn truncated_on_eof(e: std::io::Error) -> HandleError
+{
+   if e.kind() == std::io::ErrorKind::UnexpectedEof { HandleError::Truncated } else { HandleError::Syscall(e) }
+}
+
+fn read_exact_vec<R: Read>(r: &mut R, len: u32) -> std::io::Result<Vec<u8>>
+{
+   let len = len as usize;
+   let mut result = Vec::<u8>::new();
+   result.try_reserve(len)?;
+   result.resize(len, 0);
+   r.read_exact(&mut result)?;
+   Ok(result)
+}
+
+impl LinuxFileHandle
+{
+   /// Writes the handle as a frame: a little-endian ```u32``` length followed by the bytes returned by ```get_vec()```
+   pub fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()>
+   {
+      let bytes = self.get_vec()?;
+      let len: u32 = match bytes.len().try_into()
+      {
+         Ok(l) => l,
+         Err(_) => return Err(invalid_data("file handle is too large to frame")),
+      };
+      w.write_all(&len.to_le_bytes())?;
+      w.write_all(&bytes)
+   }
+
+   /// Reads a single frame written by ```write_to()```, returning None if the reader is at end-of-file before the frame starts
+   ///
+   /// An end-of-file anywhere inside the frame (e.g. a connection closed in the middle of a handle) is reported as ```Truncated```, and frames with a length that cannot belong to a file handle are rejected as ```Malformed```
+   pub fn read_from<R: Read>(r: &mut R) -> Result<Option<LinuxFileHandle>,HandleError>
+   {
+      let len = match read_u32_or_eof(r).map_err(truncated_on_eof)?
+      {
+         Some(l) => l,
+         None => return Ok(None),
+      };
+      if !(8..=MAX_FRAME_LEN).contains(&len) { return Err(HandleError::Malformed("invalid file handle frame length")); }
+      let bytes = read_exact_vec(r, len).map_err(truncated_on_eof)?;
+      Ok(Some(LinuxFileHandle::from_vec_impl(&bytes)?))
+   }
+
+   /// Iterates over the frames of a stream with ```read_from()```, ending cleanly at an end-of-file between two frames
+   ///
+   /// An error (like ```Truncated``` for an end-of-file inside a frame) is yielded once, and ends the iteration
+   pub fn iter_frames_from<R: Read>(mut r: R) -> impl Iterator<Item = Result<LinuxFileHandle,HandleError>>
+   {
+      let mut done = false;
+      std::iter::from_fn(move || {
+         if done { return None; }
+         match LinuxFileHandle::read_from(&mut r)
+         {
+            Ok(Some(fh)) => Some(Ok(fh)),
+            Ok(None) => { done = true; None },
+            Err(e) => { done = true; Some(Err(e)) },
+         }
+      })
+   }
+}
+
+impl LinuxFileHandle
+{
+   /// Serializes the handle in a layout that does not depend on the byte order of the machine, e.g. for handles exchanged between the servers of a cluster or stored on disk and read on another architecture
+   ///
+   /// The bytes are ```handle_bytes``` (big-endian ```u32```), ```handle_type``` (big-endian ```u32```, like ```get_handle_type()```) and the ```handle_bytes``` bytes of ```f_handle```, in the order the kernel gave them. The mount ID is not included
+   pub fn to_wire_bytes(&self) -> Result<Vec<u8>,TryReserveError>
+   {
+      let handle_bytes = self.v.first().copied().unwrap_or(0);
+      let mut result = Vec::<u8>::new();
+      result.try_reserve(8 + handle_bytes as usize)?;
+      result.extend_from_slice(&handle_bytes.to_be_bytes());
+      result.extend_from_slice(&self.get_handle_type().to_be_bytes());
+      result.extend(self.payload_iter());
+      Ok(result)
+   }
+
+   /// Creates a handle from the bytes produced by ```to_wire_bytes()``` on any machine
+   ///
+   /// Fails with ```HandleBytesTooLarge``` if ```handle_bytes``` is larger than ```MAX_HANDLE_SZ```, and with ```Malformed``` if the header is incomplete or if ```handle_bytes``` does not match the number of bytes that follow
+   pub fn from_wire_bytes(src: &[u8]) -> Result<LinuxFileHandle,HandleError>
+   {
+      let (header, f_handle) = match src.split_first_chunk::<8>()
+      {
+         Some(h) => h,
+         None => return Err(HandleError::Malformed("incomplete wire header")),
+      };
+      let handle_bytes = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+      let handle_type = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+      if handle_bytes > MAX_HANDLE_SZ { return Err(HandleError::HandleBytesTooLarge(LinuxFileHandle::get_usize(handle_bytes)?)); }
+      if handle_bytes as usize != f_handle.len() { return Err(HandleError::Malformed("handle_bytes does not match the wire payload")); }
+      LinuxFileHandle::from_raw(handle_type, f_handle)
+   }
+
+   /// Packs the handle, its mount ID, the UUID of its file-system (if ```fs_uuid()``` can resolve it) and the path it was obtained from into one versioned record, which a machine can turn back into a handle to open, and a human can inspect to see what the handle referred to
+   ///
+   /// A relative ```path_used``` is resolved against ```dirfd``` (or the current directory if it is None), so that the record stays meaningful without the file descriptor. The record consists of a version byte, a flags byte, the mount ID (little-endian ```i32```, -1 if none), the UUID (16 bytes, only if the flags say so), the path (little-endian ```u32``` length and raw bytes) and the handle frame produced by ```write_to()```
+   pub fn to_described_record(&self, dirfd: Option<BorrowedFd<'_>>, path_used: impl AsRef<Path>) -> Result<Vec<u8>,HandleError>
+   {
+      let path_used = path_used.as_ref();
+      let path = if path_used.is_absolute()
+      {
+         PathBuf::from(path_used)
+      }
+      else
+      {
+         let base = match dirfd
+         {
+            Some(fd) => std::fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd()))?,
+            None => std::env::current_dir()?,
+         };
+         base.join(path_used)
+      };
+      let path_bytes = path.as_os_str().as_bytes();
+      let path_len: u32 = match path_bytes.len().try_into()
+      {
+         Ok(l) if l <= MAX_PATH_LEN => l,
+         _ => return Err(HandleError::Malformed("path is too long for the record")),
+      };
+      let fs_uuid = self.fs_uuid().ok().flatten();
+      let mut result = Vec::<u8>::new();
+      result.try_reserve(2 + 4 + 16 + 4 + path_bytes.len() + 4 + self.handle_len())?;
+      result.push(DESCRIBED_RECORD_VERSION);
+      result.push(if fs_uuid.is_some() { DESCRIBED_RECORD_HAS_UUID } else { 0 });
+      result.extend_from_slice(&self.mnt_id.to_le_bytes());
+      if let Some(uuid) = fs_uuid { result.extend_from_slice(&uuid); }
+      result.extend_from_slice(&path_len.to_le_bytes());
+      result.extend_from_slice(path_bytes);
+      self.write_to(&mut result)?;
+      Ok(result)
+   }
+
+   /// Parses a record written by ```to_described_record()```
+   pub fn from_described_record(record: &[u8]) -> Result<DescribedRecord,HandleError>
+   {
+      let mut r = record;
+      let mut header: [u8; 6] = [0; 6];
+      r.read_exact(&mut header).map_err(|_| HandleError::Malformed("truncated record"))?;
+      if header[0] != DESCRIBED_RECORD_VERSION { return Err(HandleError::Malformed("unsupported record version")); }
+      if header[1] & !DESCRIBED_RECORD_HAS_UUID != 0 { return Err(HandleError::Malformed("unknown record flags")); }
+      let mnt_id = i32::from_le_bytes([header[2], header[3], header[4], header[5]]);
+      let fs_uuid = if header[1] & DESCRIBED_RECORD_HAS_UUID != 0
+      {
+         let mut uuid: [u8; 16] = [0; 16];
+         r.read_exact(&mut uuid).map_err(|_| HandleError::Malformed("truncated record"))?;
+         Some(uuid)
+      }
+      else
+      {
+         None
+      };
+      let path_len = match read_u32_or_eof(&mut r)
+      {
+         Ok(Some(l)) if l <= MAX_PATH_LEN => l,
+         Ok(Some(_)) => return Err(HandleError::Malformed("invalid path length in record")),
+         _ => return Err(HandleError::Malformed("truncated record")),
+      };
+      let path = PathBuf::from(OsString::from_vec(read_exact_vec(&mut r, path_len).map_err(|_| HandleError::Malformed("truncated record"))?));
+      let mut handle = match LinuxFileHandle::read_from(&mut r)
+      {
+         Ok(Some(h)) => h,
+         Ok(None) | Err(HandleError::Truncated) => return Err(HandleError::Malformed("truncated record")),
+         Err(e) => return Err(e),
+      };
+      if !r.is_empty() { return Err(HandleError::Malformed("trailing bytes after record")); }
+      handle.mnt_id = if mnt_id >= 0 { mnt_id } else { -1 };
+      Ok(DescribedRecord { handle, fs_uuid, path })
+   }
+}
+
+/// Computes the exact number of bytes ```pack_many()``` produces for ```handles```, e.g. to allocate the storage for them at once
+pub fn packed_size(handles: &[LinuxFileHandle]) -> usize
+{
+   4 + handles.iter().map(|h| 4 + h.handle_len()).sum::<usize>()
+}
+
+/// Packs a set of handles into one buffer: a little-endian ```u32``` count followed by the frames produced by ```write_to()```
+pub fn pack_many(handles: &[LinuxFileHandle]) -> std::io::Result<Vec<u8>>
+{
+   let count: u32 = match handles.len().try_into()
+   {
+      Ok(c) => c,
+      Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "too many file handles to pack")),
+   };
+   let mut result = Vec::<u8>::new();
+   result.try_reserve_exact(packed_size(handles))?;
+   result.extend_from_slice(&count.to_le_bytes());
+   for handle in handles
+   {
+      handle.write_to(&mut result)?;
+   }
+   Ok(result)
+}
+
+/// Unpacks a buffer produced by ```pack_many()```, which must not have anything after the last frame
+pub fn unpack_many(mut bytes: &[u8]) -> std::io::Result<Vec<LinuxFileHandle>>
+{
+   let count = match read_u32_or_eof(&mut bytes)?
+   {
+      Some(c) => c as usize,
+      None => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+   };
+   // every frame takes at least 12 bytes, a bogus count must not make us reserve more than the input can hold
+   if count > bytes.len() / 12 { return Err(invalid_data("file handle count exceeds the packed data")); }
+   let mut result = Vec::<LinuxFileHandle>::new();
+   result.try_reserve_exact(count)?;
+   for _ in 0..count
+   {
+      match LinuxFileHandle::read_from(&mut bytes)?
+      {
+         Some(h) => result.push(h),
+         None => return Err(HandleError::Truncated.into()),
+      }
+   }
+   if !bytes.is_empty() { return Err(invalid_data("trailing bytes after packed file handles")); }
+   Ok(result)
+}
+
+/// Writes an archive of (path, handle) pairs, e.g. a snapshot of all handles in a directory tree
+///
+/// Every entry is stored as a little-endian ```u32``` path length, the raw path bytes and the handle frame produced by ```write_to()```. The archive has no header and ends where the last entry ends
+pub fn write_archive<W: Write>(entries: &[(PathBuf, LinuxFileHandle)], w: &mut W) -> std::io::Result<()>
+{
+   for (path, handle) in entries
+   {
+      let path_bytes = path.as_os_str().as_bytes();
+      let len: u32 = match path_bytes.len().try_into()
+      {
+         Ok(l) if l <= MAX_PATH_LEN => l,
+         _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "path is too long for the archive")),
+      };
+      w.write_all(&len.to_le_bytes())?;
+      w.write_all(path_bytes)?;
+      handle.write_to(w)?;
+   }
+   Ok(())
+}
+
+/// Reads an archive written by ```write_archive()```
+///
+/// Reading stops at an end-of-file between entries. An end-of-file inside an entry, or a path or handle length that is out of range, is reported as an error
+pub fn read_archive<R: Read>(r: &mut R) -> std::io::Result<Vec<(PathBuf, LinuxFileHandle)>>
+{
+   let mut result = Vec::<(PathBuf, LinuxFileHandle)>::new();
+   while let Some(len) = read_u32_or_eof(r)?
+   {
+      if len > MAX_PATH_LEN { return Err(invalid_data("invalid path length in archive")); }
+      let path = PathBuf::from(OsString::from_vec(read_exact_vec(r, len)?));
+      let handle = match LinuxFileHandle::read_from(r)?
+      {
+         Some(h) => h,
+         None => return Err(HandleError::Truncated.into()),
+      };
+      result.try_reserve(1)?;
+      result.push((path, handle));
+   }
+   Ok(result)
+}
@@ -0,0 +1,23 @@
+//! Unpadded lowercase base32 encoding (RFC 4648 alphabet) of byte strings
+
+/// Appends the unpadded lowercase base32 representation of ```bytes``` to ```out```
+pub(crate) fn encode_into(out: &mut String, bytes: &[u8])
+{
+   const DIGITS: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+   let mut buffer: u32 = 0;
+   let mut bits: u32 = 0;
+   for b in bytes
+   {
+      buffer = (buffer << 8) | u32::from(*b);
+      bits += 8;
+      while bits >= 5
+      {
+         bits -= 5;
+         out.push(DIGITS[((buffer >> bits) & 0x1f) as usize] as char);
+      }
+   }
+   if bits > 0
+   {
+      out.push(DIGITS[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+   }
+}
@@ -0,0 +1,66 @@
+//! Handles bundled with the file descriptor they were obtained from
+use std::os::fd::AsFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::OwnedFd;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+
+/// A file handle together with the borrowed file descriptor it was obtained from, created by ```LinuxFileHandle::obtain_fd_bound()```
+///
+/// The descriptor is on the same file-system as the file, so it is a valid ```mnt_fd``` for ```open_by_handle()```, and the borrow keeps it open for as long as the handle is used with it
+pub struct HandleWithMount<'fd>
+{
+   handle: LinuxFileHandle,
+   fd: BorrowedFd<'fd>,
+}
+
+impl<'fd> HandleWithMount<'fd>
+{
+   /// Access the handle
+   pub fn handle(&self) -> &LinuxFileHandle
+   {
+      &self.handle
+   }
+
+   /// Access the file descriptor the handle was obtained from
+   pub fn mount_fd(&self) -> BorrowedFd<'fd>
+   {
+      self.fd
+   }
+
+   /// Releases the borrow, keeping only the handle
+   pub fn into_handle(self) -> LinuxFileHandle
+   {
+      self.handle
+   }
+
+   /// Same as ```LinuxFileHandle::open_by_handle()```, with the captured file descriptor as ```mnt_fd```
+   ///
+   /// # Safety
+   ///
+   /// See ```LinuxFileHandle::open_by_handle()```
+   pub unsafe fn open(&self, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   {
+      unsafe { self.handle.open_by_handle(self.fd, flags) }
+   }
+}
+
+impl AsFd for HandleWithMount<'_>
+{
+   fn as_fd(&self) -> BorrowedFd<'_>
+   {
+      self.fd
+   }
+}
+
+impl LinuxFileHandle
+{
+   /// Same as ```obtain_fd()```, but keeps the borrowed file descriptor along with the handle, so that it can be opened without supplying ```mnt_fd``` again
+   ///
+   /// ```open_by_handle_at()``` does not accept ```O_PATH``` descriptors as ```mnt_fd```: if ```fd``` is one, the handle is still obtained, but ```HandleWithMount::open()``` fails with ```EBADF```
+   pub fn obtain_fd_bound(fd: BorrowedFd<'_>) -> std::io::Result<HandleWithMount<'_>>
+   {
+      let handle = LinuxFileHandle::obtain_fd(Some(fd))?;
+      Ok(HandleWithMount { handle, fd })
+   }
+}
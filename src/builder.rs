@@ -0,0 +1,147 @@
+//! A builder combining the options of the ```obtain*()``` functions
+use std::marker::PhantomData;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::path::Path;
+use std::os::unix::ffi::OsStrExt;
+use crate::AtFlags;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+use crate::ffi_bindings::*;
+
+/// Marker of an ```ObtainBuilder``` that only obtains the handle of the file
+pub struct NoParent;
+
+/// Marker of an ```ObtainBuilder``` that also obtains the handle of the parent directory
+pub struct WithParent;
+
+/// A builder for obtaining a handle with several options at once, created by ```LinuxFileHandle::obtain_builder()```
+///
+/// The type parameter records whether ```with_parent()``` was called, so that ```run()``` returns either the handle or the handles of the file and of its parent directory
+pub struct ObtainBuilder<'a, P = NoParent>
+{
+   dirfd: Option<BorrowedFd<'a>>,
+   path: &'a Path,
+   flags: AtFlags,
+   initial_words: usize,
+   verify: Option<BorrowedFd<'a>>,
+   parent: PhantomData<P>,
+}
+
+impl<'a, P> ObtainBuilder<'a, P>
+{
+   /// Sets the directory a relative path is resolved from (the current directory by default)
+   pub fn dir(mut self, dirfd: BorrowedFd<'a>) -> Self
+   {
+      self.dirfd = Some(dirfd);
+      self
+   }
+
+   /// Sets the path of the file
+   pub fn path<S: AsRef<Path> + ?Sized>(mut self, path: &'a S) -> Self
+   {
+      self.path = path.as_ref();
+      self
+   }
+
+   /// Sets the flags ```name_to_handle_at()``` is called with (```AT_EMPTY_PATH``` with an empty path obtains the handle of the directory file descriptor itself)
+   pub fn flags(mut self, flags: AtFlags) -> Self
+   {
+      self.flags = flags;
+      self
+   }
+
+   /// Makes room for ```initial_words``` words of payload in the first call to ```name_to_handle_at()```, see ```obtain_with_initial_words()```
+   pub fn initial_words(mut self, initial_words: usize) -> Self
+   {
+      self.initial_words = initial_words;
+      self
+   }
+
+   /// Opens the obtained handle with ```O_PATH``` on ```mnt_fd``` and fails unless it opens the same i-node as the path (the descriptor is closed right away). This requires ```CAP_DAC_READ_SEARCH```, just like ```open_by_handle()```
+   ///
+   /// ```mnt_fd``` must not be an ```O_PATH``` descriptor, which ```open_by_handle_at()``` does not accept. When the handle of the parent is obtained as well, only the handle of the file is verified
+   pub fn verify(mut self, mnt_fd: BorrowedFd<'a>) -> Self
+   {
+      self.verify = Some(mnt_fd);
+      self
+   }
+
+   /// Also obtains the handle of the directory containing the last component of the path as it is written (the root directory is its own parent). A trailing symbolic link does not change the parent, even with ```AT_SYMLINK_FOLLOW```
+   pub fn with_parent(self) -> ObtainBuilder<'a, WithParent>
+   {
+      ObtainBuilder { dirfd: self.dirfd, path: self.path, flags: self.flags, initial_words: self.initial_words, verify: self.verify, parent: PhantomData }
+   }
+
+   fn obtain_file(&self) -> std::io::Result<LinuxFileHandle>
+   {
+      let fh = LinuxFileHandle::obtain_impl_hinted(self.dirfd, self.path.as_os_str().as_bytes(), self.flags, self.initial_words)?;
+      if let Some(mnt_fd) = self.verify { self.verify_handle(&fh, mnt_fd)?; }
+      Ok(fh)
+   }
+
+   fn verify_handle(&self, fh: &LinuxFileHandle, mnt_fd: BorrowedFd<'_>) -> std::io::Result<()>
+   {
+      let d_fd = match self.dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      let mut raw_stat_flags = LinuxFileHandle::get_signed((self.flags & AtFlags::AT_EMPTY_PATH).bits())?;
+      if !self.flags.contains(AtFlags::AT_SYMLINK_FOLLOW) { raw_stat_flags |= LinuxFileHandle::get_signed(AT_SYMLINK_NOFOLLOW)?; }
+      let path_v = LinuxFileHandle::c_path(self.path.as_os_str().as_bytes())?;
+      let mut expected = std::mem::MaybeUninit::<stat>::uninit();
+      if unsafe { fstatat(d_fd, path_v.as_ptr() as *const i8, expected.as_mut_ptr(), raw_stat_flags) } != 0 { return Err(std::io::Error::last_os_error()); }
+      let expected = unsafe { expected.assume_init() };
+      // the descriptor is closed right away, so verifying does not give access to anything
+      let reopened = unsafe { fh.open_by_handle(mnt_fd, OpenFlags::O_PATH | OpenFlags::O_CLOEXEC)? };
+      let actual = LinuxFileHandle::fstat_fd(reopened.as_fd())?;
+      if (actual.st_dev, actual.st_ino) != (expected.st_dev, expected.st_ino) { return Err(std::io::Error::other("obtained file handle opens a different file")); }
+      Ok(())
+   }
+}
+
+impl<'a> ObtainBuilder<'a, NoParent>
+{
+   pub(crate) fn new() -> ObtainBuilder<'a, NoParent>
+   {
+      ObtainBuilder { dirfd: None, path: Path::new(""), flags: AtFlags::empty(), initial_words: 0, verify: None, parent: PhantomData }
+   }
+
+   /// Obtains the handle
+   pub fn run(self) -> std::io::Result<LinuxFileHandle>
+   {
+      self.obtain_file()
+   }
+}
+
+impl<'a> ObtainBuilder<'a, WithParent>
+{
+   /// Obtains the handles of the file and of its parent directory, in this order
+   ///
+   /// Fails with ```InvalidInput``` if the path is empty, since it has no parent to resolve
+   pub fn run(self) -> std::io::Result<(LinuxFileHandle, LinuxFileHandle)>
+   {
+      if self.path.as_os_str().is_empty() { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "an empty path has no parent")); }
+      let fh = self.obtain_file()?;
+      let parent_path = match self.path.parent()
+      {
+         Some(p) if p.as_os_str().is_empty() => Path::new("."),
+         Some(p) => p,
+         None => self.path, // the root directory
+      };
+      // symbolic links in the directory part are always followed, just like during path resolution
+      let parent = LinuxFileHandle::obtain_impl(self.dirfd, parent_path.as_os_str().as_bytes(), AtFlags::AT_SYMLINK_FOLLOW)?;
+      Ok((fh, parent))
+   }
+}
+
+impl LinuxFileHandle
+{
+   /// Creates an ```ObtainBuilder```, for obtaining a handle with several options at once (```obtain()``` remains the simplest way for the common case)
+   pub fn obtain_builder<'a>() -> ObtainBuilder<'a>
+   {
+      ObtainBuilder::new()
+   }
+}
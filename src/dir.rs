@@ -0,0 +1,146 @@
+//! Handles of directory entries
+use std::ffi::CStr;
+use std::ffi::OsString;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::OsStringExt;
+use std::path::Path;
+use crate::AtFlags;
+use crate::HandleResolver;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+use crate::ffi_bindings::*;
+
+/// Size of the buffer ```getdents64()``` fills, enough for several hundred entries
+const DENTS_BUF_LEN: usize = 32 * 1024;
+
+/// Iterator returned by ```dir_handle_iter()```
+struct DirHandleIter
+{
+   dir: OwnedFd,
+   buf: Vec<u8>,
+   pos: usize,
+   len: usize,
+   done: bool,
+   resolver: HandleResolver,
+}
+
+/// Resolves names in one directory to handles, keeping a single ```O_PATH``` file descriptor for the directory
+///
+/// Servers that look up many names under the same directory handle (like an NFS server handling ```LOOKUP```) can open the directory once and reuse the descriptor, rather than opening and closing it for every lookup. The descriptor is closed when the resolver is dropped
+pub struct DirHandleResolver
+{
+   dir: OwnedFd,
+}
+
+impl DirHandleResolver
+{
+   /// Opens the directory referred to by ```dir``` with ```O_PATH```
+   ///
+   /// # Safety
+   ///
+   /// See ```LinuxFileHandle::open_by_handle()```: the resolver gives access to the names in the directory regardless of whether it is reachable by a path
+   pub unsafe fn new(dir: &LinuxFileHandle, mnt_fd: impl AsFd) -> std::io::Result<DirHandleResolver>
+   {
+      let fd = unsafe { dir.open_by_handle(mnt_fd, OpenFlags::O_PATH | OpenFlags::O_DIRECTORY | OpenFlags::O_CLOEXEC)? };
+      Ok(DirHandleResolver { dir: fd })
+   }
+
+   /// Uses an already open file descriptor of the directory (it may be an ```O_PATH``` one)
+   pub fn from_fd(dir: OwnedFd) -> DirHandleResolver
+   {
+      DirHandleResolver { dir }
+   }
+
+   /// Retrieves the handle of the entry ```name``` of the directory, without following a trailing symbolic link
+   ///
+   /// ```name``` must be a single path component: names that are empty or contain a slash are rejected with ```InvalidInput```, so that the lookup cannot leave the directory (except through ```..```, which is resolved as usual)
+   pub fn resolve(&self, name: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle>
+   {
+      let name = name.as_ref().as_os_str().as_bytes();
+      if name.is_empty() || name.contains(&b'/') { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a single path component")); }
+      LinuxFileHandle::obtain_impl(Some(self.dir.as_fd()), name, AtFlags::empty())
+   }
+}
+
+impl AsFd for DirHandleResolver
+{
+   fn as_fd(&self) -> BorrowedFd<'_>
+   {
+      self.dir.as_fd()
+   }
+}
+
+impl DirHandleIter
+{
+   /// Refills the buffer, returning false at the end of the directory
+   fn fill(&mut self) -> std::io::Result<bool>
+   {
+      let r = unsafe { getdents64(self.dir.as_raw_fd(), self.buf.as_mut_ptr() as *mut std::os::raw::c_void, self.buf.len()) };
+      if r < 0 { return Err(std::io::Error::last_os_error()); }
+      self.pos = 0;
+      self.len = r as usize;
+      Ok(r > 0)
+   }
+
+   /// Returns the name of the next entry in the buffer, refilling it when it is exhausted
+   fn next_name(&mut self) -> std::io::Result<Option<OsString>>
+   {
+      loop
+      {
+         if self.pos >= self.len && !self.fill()? { return Ok(None); }
+         let record = &self.buf[self.pos..self.len];
+         let name_offset = std::mem::offset_of!(dirent64, d_name);
+         let reclen_offset = std::mem::offset_of!(dirent64, d_reclen);
+         let reclen = usize::from(u16::from_ne_bytes([record[reclen_offset], record[reclen_offset + 1]]));
+         let name = match CStr::from_bytes_until_nul(&record[name_offset..reclen])
+         {
+            Ok(n) => n.to_bytes(),
+            Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed directory entry")),
+         };
+         self.pos += reclen;
+         if name == b"." || name == b".." { continue; }
+         let mut owned = Vec::<u8>::new();
+         owned.try_reserve(name.len())?;
+         owned.extend_from_slice(name);
+         return Ok(Some(OsString::from_vec(owned)));
+      }
+   }
+}
+
+impl Iterator for DirHandleIter
+{
+   type Item = std::io::Result<(OsString, LinuxFileHandle)>;
+
+   fn next(&mut self) -> Option<Self::Item>
+   {
+      if self.done { return None; }
+      let name = match self.next_name()
+      {
+         Ok(Some(n)) => n,
+         Ok(None) => { self.done = true; return None; },
+         Err(e) => { self.done = true; return Some(Err(e)); },
+      };
+      Some(self.resolver.resolve(Some(self.dir.as_fd()), &name).map(|fh| (name, fh)))
+   }
+}
+
+/// Streams the handles of the entries of a directory (except ```.``` and ```..```), obtaining each handle only when the iterator advances
+///
+/// The directory is reopened, so the position of ```dir``` is not affected, and is read with ```getdents64()``` into a single buffer, so the memory used is bounded by that buffer and one handle at a time, however large the directory is. Each handle is obtained with a single call to ```name_to_handle_at()``` relative to the directory, into a buffer reused for all entries (see ```HandleResolver```). Symbolic links are not followed. An entry that cannot be handled (e.g. removed since it was read) yields an error and the iteration goes on; an error reading the directory ends it
+///
+/// Just like with ```readdir()```, if entries are added to or removed from the directory during the iteration, it is unspecified whether they are returned
+pub fn dir_handle_iter(dir: BorrowedFd<'_>) -> std::io::Result<impl Iterator<Item = std::io::Result<(OsString, LinuxFileHandle)>>>
+{
+   let r = unsafe { openat(dir.as_raw_fd(), c".".as_ptr(), LinuxFileHandle::get_signed(O_RDONLY | O_DIRECTORY | O_CLOEXEC)?) };
+   if r < 0 { return Err(std::io::Error::last_os_error()); }
+   let dir = unsafe { OwnedFd::from_raw_fd(r) };
+   let mut buf = Vec::<u8>::new();
+   buf.try_reserve(DENTS_BUF_LEN)?;
+   buf.resize(DENTS_BUF_LEN, 0);
+   Ok(DirHandleIter { dir, buf, pos: 0, len: 0, done: false, resolver: HandleResolver::new() })
+}
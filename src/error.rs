@@ -0,0 +1,105 @@
+//! Error type of the operations that are not plain system calls
+use std::collections::TryReserveError;
+
+/// An error of an operation on a file handle
+///
+/// Functions returning ```std::io::Result``` turn these errors (except ```Syscall```) into a ```std::io::Error``` that wraps them, so they can still be told apart from errors of the operating system with ```e.get_ref().and_then(|e| e.downcast_ref::<HandleError>())```
+#[derive(Debug)]
+pub enum HandleError
+{
+   /// The destination cannot hold the handle: it needs ```needed``` bytes, but only ```available``` are there
+   BufferTooSmall { needed: usize, available: usize },
+   /// Memory allocation failed
+   Alloc(TryReserveError),
+   /// The input does not describe a file handle
+   Malformed(&'static str),
+   /// A system call failed
+   Syscall(std::io::Error),
+   /// The object (e.g. a socket, a pipe or an anonymous inode) is not on a file-system that can have file handles
+   NotHandleable(&'static str),
+   /// The input ended in the middle of a frame
+   Truncated,
+   /// The declared ```handle_bytes``` (or the length of the payload) is larger than ```MAX_HANDLE_SZ```, which the kernel never hands out
+   HandleBytesTooLarge(usize),
+   /// A value (e.g. flags or a size) does not fit the integer type the system call takes
+   Conversion,
+   /// The open flags contradict each other, or cannot work with ```open_by_handle_at()```
+   InvalidFlags(&'static str),
+   /// The kernel is too old for the requested flag (e.g. ```AT_HANDLE_FID```) and rejected it
+   FlagUnsupported(&'static str),
+   /// ```open_by_handle_at()``` failed with ```EPERM``` (kept as the source) while ```CAP_DAC_READ_SEARCH``` is not effective, see ```has_open_capability()```
+   MissingCapability(std::io::Error),
+}
+
+impl std::fmt::Display for HandleError
+{
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+   {
+      match self
+      {
+         HandleError::BufferTooSmall { needed, available } => write!(f, "buffer too small for file handle: {} bytes needed, {} available", needed, available),
+         HandleError::Alloc(e) => write!(f, "memory allocation failed: {}", e),
+         HandleError::Malformed(msg) => write!(f, "malformed file handle: {}", msg),
+         HandleError::Syscall(e) => write!(f, "system call failed: {}", e),
+         HandleError::NotHandleable(kind) => write!(f, "{} has no file handle, it is not on a file-system that supports them", kind),
+         HandleError::Truncated => write!(f, "input ended in the middle of a file handle frame"),
+         HandleError::HandleBytesTooLarge(n) => write!(f, "handle_bytes {} is larger than MAX_HANDLE_SZ", n),
+         HandleError::Conversion => write!(f, "value out of range of the system call argument"),
+         HandleError::InvalidFlags(msg) => write!(f, "invalid open flags: {}", msg),
+         HandleError::FlagUnsupported(flag) => write!(f, "{} is not supported by the kernel", flag),
+         HandleError::MissingCapability(e) => write!(f, "{} (opening file handles requires CAP_DAC_READ_SEARCH, which is not effective)", e),
+      }
+   }
+}
+
+impl std::error::Error for HandleError
+{
+   fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+   {
+      match self
+      {
+         HandleError::Alloc(e) => Some(e),
+         HandleError::Syscall(e) => Some(e),
+         HandleError::MissingCapability(e) => Some(e),
+         _ => None,
+      }
+   }
+}
+
+impl From<TryReserveError> for HandleError
+{
+   fn from(e: TryReserveError) -> HandleError
+   {
+      HandleError::Alloc(e)
+   }
+}
+
+impl From<std::io::Error> for HandleError
+{
+   fn from(e: std::io::Error) -> HandleError
+   {
+      HandleError::Syscall(e)
+   }
+}
+
+impl From<HandleError> for std::io::Error
+{
+   fn from(e: HandleError) -> std::io::Error
+   {
+      let kind = match e
+      {
+         HandleError::Syscall(e) => return e,
+         HandleError::BufferTooSmall { .. } => std::io::ErrorKind::InvalidInput,
+         HandleError::Alloc(_) => std::io::ErrorKind::OutOfMemory,
+         HandleError::Malformed(_) => std::io::ErrorKind::InvalidData,
+         HandleError::NotHandleable(_) => std::io::ErrorKind::Unsupported,
+         HandleError::Truncated => std::io::ErrorKind::UnexpectedEof,
+         HandleError::HandleBytesTooLarge(_) => std::io::ErrorKind::InvalidData,
+         HandleError::Conversion => std::io::ErrorKind::InvalidInput,
+         HandleError::InvalidFlags(_) => std::io::ErrorKind::InvalidInput,
+         HandleError::FlagUnsupported(_) => std::io::ErrorKind::Unsupported,
+         HandleError::MissingCapability(_) => std::io::ErrorKind::PermissionDenied,
+      };
+      std::io::Error::new(kind, e)
+   }
+}
@@ -0,0 +1,64 @@
+//! Known values of ```handle_type```, see ```enum fid_type``` in ```include/linux/exportfs.h``` of the kernel
+
+/// 32-bit inode number and generation (ext2/3/4 and many others)
+pub(crate) const FILEID_INO32_GEN: u32 = 0x01;
+/// Same as ```FILEID_INO32_GEN```, followed by the inode number and generation of the parent directory
+pub(crate) const FILEID_INO32_GEN_PARENT: u32 = 0x02;
+/// 64-bit inode number and 32-bit generation (e.g. XFS with 64-bit inodes)
+pub(crate) const FILEID_INO64_GEN: u32 = 0x81;
+/// Same as ```FILEID_INO64_GEN```, followed by the inode number and generation of the parent directory
+pub(crate) const FILEID_INO64_GEN_PARENT: u32 = 0x82;
+/// Handle type of overlayfs handles that embed a lower-layer handle (```OVL_FILEID_V0```)
+pub(crate) const OVL_FILEID_V0: u32 = 0xfb;
+/// Handle type of overlayfs handles with an aligned header (```OVL_FILEID_V1```)
+pub(crate) const OVL_FILEID_V1: u32 = 0xf8;
+
+/// Returns the kernel name of a known handle type
+pub(crate) fn name(handle_type: u32) -> Option<&'static str>
+{
+   match handle_type
+   {
+      0x00 => Some("FILEID_ROOT"),
+      FILEID_INO32_GEN => Some("FILEID_INO32_GEN"),
+      FILEID_INO32_GEN_PARENT => Some("FILEID_INO32_GEN_PARENT"),
+      0x4d => Some("FILEID_BTRFS_WITHOUT_PARENT"),
+      0x4e => Some("FILEID_BTRFS_WITH_PARENT"),
+      0x4f => Some("FILEID_BTRFS_WITH_PARENT_ROOT"),
+      0x51 => Some("FILEID_UDF_WITHOUT_PARENT"),
+      0x52 => Some("FILEID_UDF_WITH_PARENT"),
+      0x61 => Some("FILEID_NILFS_WITHOUT_PARENT"),
+      0x62 => Some("FILEID_NILFS_WITH_PARENT"),
+      0x71 => Some("FILEID_FAT_WITHOUT_PARENT"),
+      0x72 => Some("FILEID_FAT_WITH_PARENT"),
+      FILEID_INO64_GEN => Some("FILEID_INO64_GEN"),
+      FILEID_INO64_GEN_PARENT => Some("FILEID_INO64_GEN_PARENT"),
+      0x97 => Some("FILEID_LUSTRE"),
+      0xb1 => Some("FILEID_BCACHEFS_WITHOUT_PARENT"),
+      0xb2 => Some("FILEID_BCACHEFS_WITH_PARENT"),
+      OVL_FILEID_V1 => Some("OVL_FILEID_V1"),
+      OVL_FILEID_V0 => Some("OVL_FILEID_V0"),
+      0xfe => Some("FILEID_KERNFS"),
+      0xff => Some("FILEID_INVALID"),
+      _ => None,
+   }
+}
+
+fn word(payload: &[u8], index: usize) -> Option<u32>
+{
+   let bytes = payload.get(index * 4..index * 4 + 4)?;
+   Some(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decodes the inode number and generation from the payload of a handle in one of the generic formats
+pub(crate) fn decode_ino_gen(handle_type: u32, payload: &[u8]) -> Option<(u64, u32)>
+{
+   match handle_type
+   {
+      FILEID_INO32_GEN | FILEID_INO32_GEN_PARENT => Some((u64::from(word(payload, 0)?), word(payload, 1)?)),
+      FILEID_INO64_GEN | FILEID_INO64_GEN_PARENT => {
+         let ino: [u8; 8] = payload.get(0..8)?.try_into().ok()?;
+         Some((u64::from_ne_bytes(ino), word(payload, 2)?))
+      },
+      _ => None,
+   }
+}
@@ -0,0 +1,27 @@
+//! Lowercase hex encoding of byte strings
+
+/// Appends the lowercase hex representation of ```bytes``` to ```out```
+pub(crate) fn encode_into(out: &mut String, bytes: impl Iterator<Item = u8>)
+{
+   const DIGITS: &[u8; 16] = b"0123456789abcdef";
+   for b in bytes
+   {
+      out.push(DIGITS[usize::from(b >> 4)] as char);
+      out.push(DIGITS[usize::from(b & 0xf)] as char);
+   }
+}
+
+/// Decodes a hex string (either case), returning None if it has an odd length or contains anything but hex digits
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>>
+{
+   if s.len() % 2 == 1 { return None; }
+   let mut result = Vec::<u8>::new();
+   result.try_reserve(s.len() / 2).ok()?;
+   for pair in s.as_bytes().chunks(2)
+   {
+      let high = char::from(pair[0]).to_digit(16)?;
+      let low = char::from(pair[1]).to_digit(16)?;
+      result.push((high * 16 + low) as u8);
+   }
+   Some(result)
+}
@@ -0,0 +1,113 @@
+//! Sharing of the storage of identical handles
+use std::collections::HashSet;
+use std::os::fd::AsFd;
+use std::os::fd::OwnedFd;
+use std::sync::Arc;
+use std::sync::Mutex;
+use crate::HandleRef;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+
+/// Interns handles, so that identical handles (e.g. of a popular file looked up by many clients) share their storage
+///
+/// Handles are identical if they have the same ```handle_type``` and payload, the mount ID is kept per ```InternedHandle```. The interner keeps the storage of every handle it has seen until ```purge()``` is called
+pub struct HandleInterner
+{
+   words: Mutex<HashSet<Arc<[u32]>>>,
+}
+
+/// A handle whose words are shared with identical handles interned by the same ```HandleInterner```
+///
+/// The read-only accessors are provided by ```view()``` rather than by ```Deref```: a ```HandleRef``` borrows the words, so it cannot be the target of ```Deref``` without being stored in the very handle it borrows from. Making a view is free, since it only copies a reference and the mount ID
+#[derive(Clone)]
+pub struct InternedHandle
+{
+   words: Arc<[u32]>,
+   mnt_id: i32,
+}
+
+impl HandleInterner
+{
+   /// Creates an empty interner
+   pub fn new() -> HandleInterner
+   {
+      HandleInterner { words: Mutex::new(HashSet::new()) }
+   }
+
+   /// Returns an ```InternedHandle``` for ```handle```, sharing the words of an identical handle interned before if there is one
+   pub fn intern(&self, handle: &LinuxFileHandle) -> InternedHandle
+   {
+      // bytes past handle_bytes are padding, which must not make identical handles differ (just like with ```==```)
+      let handle_bytes = handle.v.first().copied().unwrap_or(0) as usize;
+      let used = (2 + handle_bytes.div_ceil(4)).min(handle.v.len());
+      let mut key = handle.v[..used].to_vec();
+      if !handle_bytes.is_multiple_of(4) && used == 2 + handle_bytes.div_ceil(4)
+      {
+         let mut last = key[used - 1].to_ne_bytes();
+         last[handle_bytes % 4..].fill(0);
+         key[used - 1] = u32::from_ne_bytes(last);
+      }
+      let mut words = self.words.lock().unwrap_or_else(|e| e.into_inner());
+      let shared = match words.get(key.as_slice())
+      {
+         Some(w) => Arc::clone(w),
+         None => {
+            let w: Arc<[u32]> = Arc::from(key);
+            words.insert(Arc::clone(&w));
+            w
+         },
+      };
+      InternedHandle { words: shared, mnt_id: handle.mnt_id }
+   }
+
+   /// Drops the storage of the handles that are no longer referenced by any ```InternedHandle```
+   pub fn purge(&self)
+   {
+      self.words.lock().unwrap_or_else(|e| e.into_inner()).retain(|w| Arc::strong_count(w) > 1);
+   }
+
+   /// Number of distinct handles the interner keeps storage for
+   pub fn len(&self) -> usize
+   {
+      self.words.lock().unwrap_or_else(|e| e.into_inner()).len()
+   }
+
+   /// Checks whether the interner keeps no storage
+   pub fn is_empty(&self) -> bool
+   {
+      self.len() == 0
+   }
+}
+
+impl Default for HandleInterner
+{
+   fn default() -> HandleInterner
+   {
+      HandleInterner::new()
+   }
+}
+
+impl InternedHandle
+{
+   /// Borrows the handle as a ```HandleRef```, which provides the read-only accessors
+   pub fn view(&self) -> HandleRef<'_>
+   {
+      HandleRef::with_mnt_id(&self.words, self.mnt_id)
+   }
+
+   /// Checks whether two interned handles share their storage
+   pub fn shares_storage(&self, other: &InternedHandle) -> bool
+   {
+      Arc::ptr_eq(&self.words, &other.words)
+   }
+
+   /// Same as ```LinuxFileHandle::open_by_handle()```
+   ///
+   /// # Safety
+   ///
+   /// See ```LinuxFileHandle::open_by_handle()```
+   pub unsafe fn open_by_handle(&self, mnt_fd: impl AsFd, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   {
+      unsafe { self.view().open_by_handle(mnt_fd, flags) }
+   }
+}
@@ -7,23 +7,96 @@
 //! These system calls can be used to refer to i-nodes on the file system using a byte array that does not change during i-node lifetime
 //!
 //! This crate can be useful for user-space NFS servers (since NFS protocols require such references) and fanotify users wanting to refer to watched files by handles
+//!
+//! # Features
+//!
+//! * ```bumpalo```: ```LinuxFileHandle::obtain_in_arena()```, which places handles into a ```bumpalo``` arena
+//! * ```serde```: ```Serialize``` and ```Deserialize``` for ```LinuxFileHandle```, using the layout of ```to_wire_bytes()``` (without the mount ID)
+//! * ```tokio```: ```LinuxFileHandle::obtain_async()``` and ```LinuxFileHandle::open_by_handle_async()```, which make the system calls on the blocking thread pool of ```tokio```
+//! * ```no-size-probe```: every ```obtain*()``` call makes its first call to ```name_to_handle_at()``` with a buffer large enough for any handle (like ```obtain_fast()```), so that normally a single call is made and no ```EOVERFLOW``` shows up in system call logs or seccomp audits. If the kernel still asks for a larger buffer, it is grown as usual
+//! * ```verify-on-obtain```: every successful ```obtain*()``` call also opens the handle with ```O_PATH``` and fails unless it opens the same i-node, which turns file-systems that export broken handles into a loud failure in CI. It makes obtaining handles require ```CAP_DAC_READ_SEARCH``` (just like ```open_by_handle()```), so it should only be enabled for testing
 use std::os::fd::BorrowedFd;
 use std::vec::Vec;
 use std::os::fd::OwnedFd;
 use std::os::fd::AsRawFd;
 use std::os::fd::FromRawFd;
+use std::os::fd::AsFd;
+use std::io::Read;
 use std::convert::TryFrom;
+use std::path::Path;
+use std::path::PathBuf;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use bitflags::bitflags;
 mod ffi_bindings;
 use crate::ffi_bindings::*;
+mod archive;
+pub use crate::archive::*;
+mod probe;
+pub use crate::probe::*;
+mod fileid;
+mod hex;
+mod base32;
+mod nfsd;
+mod mount;
+pub use crate::mount::*;
+mod view;
+pub use crate::view::*;
+mod error;
+pub use crate::error::*;
+mod dir;
+pub use crate::dir::*;
+mod builder;
+pub use crate::builder::*;
+mod map;
+pub use crate::map::*;
+mod intern;
+pub use crate::intern::*;
+mod resolver;
+pub use crate::resolver::*;
+mod bound;
+pub use crate::bound::*;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "tokio")]
+mod tokio_impl;
 use std::collections::TryReserveError;
 
+/// Number of payload bytes included in ```log_key()```
+const LOG_KEY_PREFIX_LEN: usize = 4;
+
+/// Delay before the first retry of ```obtain_with_retry()```, doubled for every further retry
+const RETRY_INITIAL_BACKOFF: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Longest delay between two retries of ```obtain_with_retry()```
+const RETRY_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Flag of ```name_to_handle_at()``` returning the 64-bit unique mount ID, see ```obtain_with_unique_mnt_id()``` (not in the headers of older C libraries)
+const AT_HANDLE_MNT_ID_UNIQUE: u32 = 0x001;
+
+/// Number of calls to ```name_to_handle_at()``` made before giving up if the size of the handle keeps changing
+const MAX_SIZING_ATTEMPTS: usize = 4;
+
+/// The bit of ```O_TMPFILE``` other than ```O_DIRECTORY``` (the constant includes both)
+const O_TMPFILE_ONLY: u32 = O_TMPFILE & !O_DIRECTORY;
+
+/// Largest ```handle_bytes``` the kernel accepts, and the largest payload of any handle it hands out
+pub const MAX_HANDLE_SZ: u32 = ffi_bindings::MAX_HANDLE_SZ;
+
+/// Number of ```u32``` words in the largest file handle: two header words and ```MAX_HANDLE_SZ``` bytes of payload
+const MAX_HANDLE_WORDS: usize = 2 + (MAX_HANDLE_SZ as usize) / 4;
+
 /// A struct representing the file handle. The file handle itself is stored on the heap, this struct only contains a pointer to it.
-#[derive(Clone)]
+///
+/// The handle is stored as ```u32``` words laid out in memory exactly like ```struct file_handle```, which keeps the alignment the system calls need. The words are only a view of that memory: the payload bytes stay in the order the kernel wrote them, see ```as_bytes()```
+///
+/// ```Clone``` is implemented with ```duplicate()``` and panics if the memory cannot be allocated, like other collections do; servers that have to survive memory pressure should call ```duplicate()```, which returns the allocation error instead
 pub struct LinuxFileHandle
 {
    v: Vec<u32>,
    mnt_id: i32,
+   at_flags: Option<AtFlags>,
+   unique_mnt_id: Option<u64>,
 }
 
 bitflags!{
@@ -54,6 +127,101 @@ bitflags!{
    }
 }
 
+impl OpenFlags
+{
+   /// Checks for combinations of flags that are a programming error, so that they fail loudly rather than silently open the file some other way: both ```O_WRONLY``` and ```O_RDWR``` (```O_RDONLY``` is no flag at all, it is the absence of both), the bit of ```O_TMPFILE``` without ```O_DIRECTORY``` or without write access, and ```O_CREAT``` with ```O_EXCL```, which can never succeed since a handle always refers to an existing file (```O_CREAT``` alone is harmless for the same reason, and is accepted)
+   ///
+   /// ```open_by_handle()``` and the functions built on it call this before the system call. Other contradictions (e.g. ```O_PATH``` with ```O_TRUNC```) are left to the kernel
+   pub fn validate(&self) -> Result<(), HandleError>
+   {
+      let bits = self.bits();
+      if bits & O_ACCMODE == O_ACCMODE { return Err(HandleError::InvalidFlags("O_WRONLY and O_RDWR are both set")); }
+      if bits & O_TMPFILE_ONLY != 0
+      {
+         if bits & O_DIRECTORY == 0 { return Err(HandleError::InvalidFlags("O_TMPFILE is set without O_DIRECTORY")); }
+         if bits & O_ACCMODE == O_RDONLY { return Err(HandleError::InvalidFlags("O_TMPFILE needs O_WRONLY or O_RDWR")); }
+      }
+      if bits & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL { return Err(HandleError::InvalidFlags("O_CREAT with O_EXCL cannot succeed on the existing file of a handle")); }
+      Ok(())
+   }
+}
+
+bitflags!{
+   /// Flags for ```name_to_handle_at()```
+   ///
+   /// Without flags, a symbolic link in the last component of the path is not followed (as with ```AT_SYMLINK_NOFOLLOW``` for other ```*at()``` system calls), and an automount point there is not triggered (as with ```AT_NO_AUTOMOUNT```). The kernel rejects both of these flags with ```EINVAL```, so they are not part of this type
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+   pub struct AtFlags: u32 {
+      const AT_SYMLINK_FOLLOW = AT_SYMLINK_FOLLOW;
+      const AT_EMPTY_PATH = AT_EMPTY_PATH;
+      const AT_HANDLE_FID = AT_HANDLE_FID;
+   }
+}
+
+bitflags!{
+   /// Access modes checked by ```obtain_if_accessible()```, see ```access(2)``` (no flags only checks that the file exists)
+   #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+   pub struct AccessMode: u32 {
+      const R_OK = R_OK;
+      const W_OK = W_OK;
+      const X_OK = X_OK;
+   }
+}
+
+/// Kind of change token computed by ```obtain_with_content_hash()```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeToken
+{
+   /// Hash of the entire contents of the file: exact, but reads the whole file
+   Content,
+   /// Hash of the size, modification time and inode number: cheap, but misses changes that preserve the size and the modification time
+   Metadata,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Feeds ```bytes``` into a 64-bit FNV-1a hash
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64
+{
+   for b in bytes
+   {
+      hash ^= u64::from(*b);
+      hash = hash.wrapping_mul(FNV_PRIME);
+   }
+   hash
+}
+
+/// Checks whether an error returned by ```open_by_handle()``` means that the file-system no longer accepts the encoding of the handle
+///
+/// A file-system upgrade or a change of its configuration (e.g. enabling 64-bit inode numbers on XFS, or the ```index``` feature of overlayfs) can change the encoding and the size of the handles for the same inode, and the kernel rejects handles in the old encoding with ```EINVAL```. Since ```open_by_handle()``` rejects structurally invalid handles itself (with ```InvalidInput```), and ```OpenFlags``` only contains valid flags, ```EINVAL``` from the kernel can be attributed to the encoding. ```ESTALE```, on the other hand, means that the file has been deleted, and is not affected by this function
+///
+/// The handle cannot be converted to the new encoding: the only recovery is to obtain a new handle from a known path of the file (e.g. one recorded by ```write_archive()```) and replace the stored one
+pub fn is_outdated_encoding(err: &std::io::Error) -> bool
+{
+   err.raw_os_error() == Some(EINVAL as i32)
+}
+
+/// Checks whether an error returned by ```open_by_handle()``` (or the functions built on it) means that the handle is stale: the file it referred to has been deleted (and its inode possibly reused with another generation), which the kernel reports with ```ESTALE```
+///
+/// This is an expected condition for a file server rather than an I/O failure: an NFS server should answer ```NFS3ERR_STALE``` (or ```NFS4ERR_STALE```), and the client will look the file up again by name. A stale handle never becomes valid again, so callers can drop it from their caches
+pub fn is_stale(err: &std::io::Error) -> bool
+{
+   err.raw_os_error() == Some(ESTALE as i32)
+}
+
+/// What ```obtain_with_policy()``` does with an absolute path if a directory file descriptor is supplied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbsolutePathPolicy
+{
+   /// Pass the path to the kernel unchanged: it is resolved from the root directory and the directory file descriptor is ignored (the usual Linux behaviour)
+   Allow,
+   /// Fail with ```InvalidInput```
+   Reject,
+   /// Strip the leading slashes, so that the path is resolved relative to the directory file descriptor
+   StripRoot,
+}
+
 impl LinuxFileHandle
 {
    /// Retrieve the ```mnt_id``` value from ```name_to_handle_at()``` (will return None for handles created from raw byte-arrays)
@@ -62,6 +230,44 @@ impl LinuxFileHandle
       if self.mnt_id >= 0 { Some(self.mnt_id) } else { None }
    }
    
+   /// Retrieve the 64-bit unique mount ID from ```obtain_with_unique_mnt_id()```, which unlike ```get_mnt_id()``` is never reused after unmounting (will return None for handles obtained otherwise, or on kernels without ```AT_HANDLE_MNT_ID_UNIQUE```)
+   pub fn get_unique_mnt_id(&self) -> Option<u64>
+   {
+      self.unique_mnt_id
+   }
+   
+   /// Retrieve the flags ```name_to_handle_at()``` was called with to obtain the handle, e.g. to record in an audit trail whether symbolic links were followed (will return None for handles created from raw byte-arrays)
+   pub fn get_at_flags(&self) -> Option<AtFlags>
+   {
+      self.at_flags
+   }
+   
+   /// Tells whether the kernel handed out the handle for ```open_by_handle()``` (```Some(true)```) or only as an identity for comparison, obtained with ```AT_HANDLE_FID``` (```Some(false)```, see ```obtain_fid()```); returns None for handles created from raw byte-arrays, whose origin is unknown
+   ///
+   /// ```Some(true)``` does not mean that opening will succeed: the file may have been removed, and opening requires ```CAP_DAC_READ_SEARCH```
+   pub fn is_openable(&self) -> Option<bool>
+   {
+      self.at_flags.map(|f| !f.contains(AtFlags::AT_HANDLE_FID))
+   }
+   
+   /// Retrieve the ```handle_type``` field of ```struct file_handle```, i.e. the second word of ```get_slice()``` (after ```handle_bytes```): a file-system specific value describing the format of the payload, which must be preserved for ```open_by_handle_at()``` to accept the handle (will return 0 for handles too short to have it)
+   pub fn get_handle_type(&self) -> u32
+   {
+      Self::words_handle_type(&self.v)
+   }
+   
+   /// Iterates over the bytes returned by ```get_vec()``` without allocating, e.g. to feed them to a hasher or a writer; there are exactly ```handle_len()``` of them
+   pub fn bytes(&self) -> impl ExactSizeIterator<Item = u8> + '_
+   {
+      self.as_bytes().iter().copied()
+   }
+   
+   /// Borrows the opaque payload (```f_handle```), exactly ```handle_bytes``` bytes long, without the header, for formats that store ```get_handle_type()``` separately; see ```from_parts()```
+   pub fn payload_bytes(&self) -> &[u8]
+   {
+      Self::words_payload_bytes(&self.v)
+   }
+   
    /// Access the bytes of file handle itself, for example, to send it to the client or save to disk
    ///
    /// The file handle should be considered an opaque value
@@ -70,26 +276,284 @@ impl LinuxFileHandle
       self.v.as_slice()
    }
    
+   /// Number of bytes of the payload, i.e. the length of ```payload_bytes()``` (without the header, unlike ```handle_len()```)
+   pub fn len(&self) -> usize
+   {
+      self.payload_bytes().len()
+   }
+   
+   /// Checks whether the handle has no payload, which can only be a placeholder or a malformed handle created from bytes: handles obtained from the kernel are never empty
+   pub fn is_empty(&self) -> bool
+   {
+      self.len() == 0
+   }
+   
+   /// Number of bytes returned by ```get_vec()``` and ```as_bytes()``` (the header and ```handle_bytes``` bytes of payload), computed without allocating, e.g. to reserve the room for the handle in a buffer
+   pub fn handle_len(&self) -> usize
+   {
+      Self::words_handle_len(&self.v)
+   }
+   
+   /// Borrows the bytes returned by ```get_vec()```, without copying them
+   pub fn as_bytes(&self) -> &[u8]
+   {
+      Self::words_as_bytes(&self.v)
+   }
+   
    /// Access the bytes of file handle itself, for example, to send it to the client or save to disk
    ///
-   /// The file handle should be considered an opaque value
+   /// The file handle should be considered an opaque value. The bytes are the header (```handle_bytes``` and ```handle_type```) followed by ```handle_bytes``` bytes of payload, without the padding of the last word
    pub fn get_vec(&self) -> Result<Vec<u8>,TryReserveError>
    {
+      Self::words_to_vec(&self.v)
+   }
+   
+   /// Encodes the bytes returned by ```get_vec()``` as lowercase hex, e.g. for configuration files and log lines (```Display``` prints the same string)
+   pub fn to_hex(&self) -> String
+   {
+      Self::words_to_hex(&self.v)
+   }
+
+   // the accessors above work on the words, so that HandleRef can share them
+
+   fn words_handle_type(v: &[u32]) -> u32
+   {
+      v.get(1).copied().unwrap_or(0)
+   }
+
+   fn words_handle_len(v: &[u32]) -> usize
+   {
+      let handle_bytes = v.first().copied().unwrap_or(0) as usize;
+      v.len().min(2) * 4 + (v.len().saturating_sub(2) * 4).min(handle_bytes)
+   }
+
+   fn words_as_bytes(v: &[u32]) -> &[u8]
+   {
+      // u32 has no padding and u8 has no alignment, so the words can be viewed as bytes in memory order
+      let all = unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * 4) };
+      &all[..Self::words_handle_len(v)]
+   }
+
+   fn words_payload_bytes(v: &[u32]) -> &[u8]
+   {
+      let bytes = Self::words_as_bytes(v);
+      &bytes[bytes.len().min(8)..]
+   }
+
+   fn words_to_vec(v: &[u32]) -> Result<Vec<u8>,TryReserveError>
+   {
+      let bytes = Self::words_as_bytes(v);
       let mut result = Vec::<u8>::new();
-      result.try_reserve(self.v.len() * 4)?;
-      for i in &self.v
+      result.try_reserve(bytes.len())?;
+      result.extend_from_slice(bytes);
+      Ok(result)
+   }
+   
+   fn words_to_hex(v: &[u32]) -> String
+   {
+      let mut result = String::new();
+      hex::encode_into(&mut result, Self::words_as_bytes(v).iter().copied());
+      result
+   }
+   
+   /// Returns a compact correlation id for tracing a handle through the logs of a server, formatted as ```<mnt_id>:<handle_type>:<payload prefix in hex>```
+   ///
+   /// Only the first bytes of the payload are included, so that logs never contain a complete handle that could be opened. The key is not collision-free and must not be used for security decisions
+   pub fn log_key(&self) -> String
+   {
+      let mut result = match self.get_mnt_id()
+      {
+         Some(id) => format!("{}:", id),
+         None => String::from("none:"),
+      };
+      result.push_str(&format!("{}:", self.get_handle_type()));
+      hex::encode_into(&mut result, self.payload_iter().take(LOG_KEY_PREFIX_LEN));
+      result
+   }
+   
+   /// Returns the bytes that identify the file within its file-system: ```handle_type``` as a little-endian ```u32```, followed by the ```handle_bytes``` bytes of the payload
+   ///
+   /// Unlike ```get_vec()```, the key neither contains the mount ID nor any padding, so two handles of the same file obtained through different mounts have the same key
+   pub fn identity_key(&self) -> Result<Vec<u8>,TryReserveError>
+   {
+      let mut result = Vec::<u8>::new();
+      result.try_reserve(4 + self.v.first().copied().unwrap_or(0) as usize)?;
+      result.extend_from_slice(&self.get_handle_type().to_le_bytes());
+      result.extend(self.payload_iter());
+      Ok(result)
+   }
+   
+   /// Returns ```identity_key()``` encoded as unpadded lowercase base32, an opaque token for logs, metrics labels and trace ids
+   ///
+   /// The token has no NUL bytes, slashes or plus signs, and can be used as a path component or URL segment; it can be compared case-insensitively. It omits the mount ID, so it identifies the file only together with the file-system it is on, and it cannot be turned back into a handle that can be opened without that context. Unlike ```log_key()```, it contains the complete payload, and so can fail to allocate just like ```identity_key()```
+   pub fn opaque_token(&self) -> Result<String,TryReserveError>
+   {
+      let key = self.identity_key()?;
+      let mut result = String::new();
+      result.try_reserve((key.len() * 8).div_ceil(5))?;
+      base32::encode_into(&mut result, &key);
+      Ok(result)
+   }
+   
+   /// Checks whether the handle was produced by overlayfs (its ```handle_type``` is ```OVL_FILEID_V0``` or ```OVL_FILEID_V1```)
+   ///
+   /// overlayfs only produces handles that can be opened if it is mounted with ```nfs_export=on``` (which in turn requires ```index=on```). These handles embed the handle of the file on the lower or upper layer, so their size depends on the underlying file-systems and may change after a copy-up. They can only be opened with ```mnt_fd``` on the overlay mount itself: a file descriptor on one of the layers refers to a different file-system
+   pub fn is_overlay_handle(&self) -> bool
+   {
+      matches!(self.get_handle_type(), fileid::OVL_FILEID_V0 | fileid::OVL_FILEID_V1)
+   }
+   
+   /// Compares the handle with a ```struct file_handle``` owned by C code, without copying it
+   ///
+   /// ```handle_bytes```, ```handle_type``` and the first ```handle_bytes``` bytes of ```f_handle``` are compared, the mount ID is not taken into account
+   ///
+   /// # Safety
+   ///
+   /// ```ptr``` must point to a readable ```struct file_handle``` (it does not have to be aligned) whose ```f_handle``` array is at least ```handle_bytes``` bytes long, and the memory must not be modified during the call
+   pub unsafe fn matches_raw_ptr(&self, ptr: *const std::ffi::c_void) -> bool
+   {
+      let base = ptr as *const u8;
+      let handle_bytes = unsafe { std::ptr::read_unaligned(base as *const u32) };
+      let handle_type = unsafe { std::ptr::read_unaligned(base.add(4) as *const u32) };
+      if self.v.first() != Some(&handle_bytes) || self.v.get(1) != Some(&handle_type) { return false; }
+      let payload = unsafe { std::slice::from_raw_parts(base.add(std::mem::size_of::<file_handle>()), handle_bytes as usize) };
+      self.payload_iter().eq(payload.iter().copied())
+   }
+   
+   /// Returns a multi-line description of the handle for interactive debugging, comparable to the output of the ```name_to_handle_at(2)``` example program and ```xfs_io``` handle commands
+   ///
+   /// The description lists ```handle_bytes```, ```handle_type``` (with its ```FILEID_*``` name if it is known), the mount ID, the payload in hex and, for the generic handle formats, the decoded inode number and generation
+   pub fn debug_dump(&self) -> String
+   {
+      let handle_type = self.get_handle_type();
+      let payload: Vec<u8> = self.payload_iter().collect();
+      let mut result = format!("handle_bytes: {}\n", self.v.first().copied().unwrap_or(0));
+      result.push_str(&format!("handle_type: {} ({})\n", handle_type, fileid::name(handle_type).unwrap_or("unknown")));
+      match self.get_mnt_id()
+      {
+         Some(id) => result.push_str(&format!("mnt_id: {}\n", id)),
+         None => result.push_str("mnt_id: none\n"),
+      }
+      result.push_str("f_handle:");
+      for b in &payload
+      {
+         result.push_str(&format!(" {:02x}", b));
+      }
+      result.push('\n');
+      if let Some((ino, generation)) = fileid::decode_ino_gen(handle_type, &payload)
       {
-         for j in i.to_ne_bytes()
+         result.push_str(&format!("inode: {}\ngeneration: {}\n", ino, generation));
+      }
+      result
+   }
+   
+   /// Describes how two handles differ, one line per difference (```handle_bytes```, ```handle_type```, mount ID and every differing payload byte by offset), or returns an empty string if they do not differ at all
+   ///
+   /// This is a purely informational debugging aid: it reports every difference, including those (like the mount ID) that do not matter when handles are compared
+   pub fn diff(&self, other: &LinuxFileHandle) -> String
+   {
+      fn describe(mnt_id: Option<i32>) -> String
+      {
+         match mnt_id
          {
-            result.push(j);
+            Some(id) => id.to_string(),
+            None => String::from("none"),
          }
       }
-      Ok(result)
+      let mut result = String::new();
+      for (index, name) in ["handle_bytes", "handle_type"].iter().enumerate()
+      {
+         let (mine, theirs) = (self.v.get(index).copied().unwrap_or(0), other.v.get(index).copied().unwrap_or(0));
+         if mine != theirs { result.push_str(&format!("{}: {} != {}\n", name, mine, theirs)); }
+      }
+      if self.get_mnt_id() != other.get_mnt_id()
+      {
+         result.push_str(&format!("mnt_id: {} != {}\n", describe(self.get_mnt_id()), describe(other.get_mnt_id())));
+      }
+      let (mine, theirs): (Vec<u8>, Vec<u8>) = (self.payload_iter().collect(), other.payload_iter().collect());
+      for offset in 0..mine.len().max(theirs.len())
+      {
+         match (mine.get(offset), theirs.get(offset))
+         {
+            (Some(a), Some(b)) if a == b => {},
+            (a, b) => result.push_str(&format!("payload[{}]: {} != {}\n", offset, a.map_or(String::from("--"), |x| format!("{:02x}", x)), b.map_or(String::from("--"), |x| format!("{:02x}", x)))),
+         }
+      }
+      result
+   }
+   
+   /// Checks whether the handles refer to the same file: they must have been obtained through the same mount (equal ```get_unique_mnt_id()``` if both have one, equal ```get_mnt_id()``` otherwise), and be equal (see ```PartialEq```)
+   ///
+   /// Returns false if either handle lacks a mount ID (e.g. handles created from bytes), since identical bytes on different file-systems are different files. Handles of the same file obtained through two different mounts of its file-system (e.g. a bind mount) are not recognized either; compare their ```fs_uuid()``` for that
+   pub fn same_file(&self, other: &LinuxFileHandle) -> bool
+   {
+      self.same_filesystem(other) == Some(true) && self == other
+   }
+   
+   /// Checks whether the handle refers to the file ```fd``` is open on, e.g. to confirm that a handle received from a client points where a file server expects before acting on it: the handle of ```fd``` is obtained (see ```obtain_fd()```), and both must be equal (see ```PartialEq```) and have the same ```get_mnt_id()```
+   ///
+   /// Returns false for handles without a 32-bit mount ID (those created from bytes, unless restored with ```from_parts_with_mnt()```, and those from ```obtain_with_unique_mnt_id()```), since identical bytes on different file-systems are different files. Fails if no handle can be obtained for ```fd```
+   pub fn matches_fd(&self, fd: impl AsFd) -> std::io::Result<bool>
+   {
+      let live = Self::obtain_fd(Some(fd.as_fd()))?;
+      Ok(self.get_mnt_id().is_some() && self.get_mnt_id() == live.get_mnt_id() && *self == live)
+   }
+   
+   /// Checks whether the handles were obtained through the same mount, comparing only the mount IDs (equal ```get_unique_mnt_id()``` if both have one, equal ```get_mnt_id()``` otherwise), e.g. to route handles to the backend serving their file-system
+   ///
+   /// Returns None if either handle has no mount ID (e.g. handles created from bytes, which cannot tell which file-system they belong to), or if one has only a unique mount ID and the other only a 32-bit one. Two mounts of the same file-system (e.g. a bind mount) have different mount IDs, so handles obtained through them give ```Some(false)```
+   pub fn same_filesystem(&self, other: &LinuxFileHandle) -> Option<bool>
+   {
+      match (self.get_unique_mnt_id(), other.get_unique_mnt_id(), self.get_mnt_id(), other.get_mnt_id())
+      {
+         (Some(a), Some(b), _, _) => Some(a == b),
+         (_, _, Some(a), Some(b)) => Some(a == b),
+         _ => None,
+      }
+   }
+   
+   /// Same as ```==```, but for handles of equal ```handle_bytes``` the time taken does not depend on where (or whether) the payloads differ, so that comparing a handle supplied by a client against a stored one does not leak the stored payload through timing
+   ///
+   /// Prefer it over ```PartialEq``` when the handles act as unguessable capability tokens; for lookups in maps and caches, where the handles are not secret, ```PartialEq``` is enough (and ```Hash``` is not constant-time anyway). A different ```handle_bytes``` returns false right away, since the length of a handle is not secret
+   pub fn ct_eq(&self, other: &LinuxFileHandle) -> bool
+   {
+      let (mine, theirs) = (self.as_bytes(), other.as_bytes());
+      if mine.len() != theirs.len() { return false; }
+      // black_box() keeps the compiler from turning the fold into a comparison that exits early
+      let diff = mine.iter().zip(theirs).fold(0u8, |acc, (a, b)| std::hint::black_box(acc | (a ^ b)));
+      diff == 0
+   }
+   
+   /// Cheap pre-check for linear scans over many handles: returns ```true``` if the handles are certainly different, looking only at ```handle_bytes```, ```handle_type``` and the first word of the payload
+   ///
+   /// ```false``` does not mean that the handles are equal, only that a full comparison of the payloads is needed. The mount ID is not compared
+   pub fn quick_ne(&self, other: &LinuxFileHandle) -> bool
+   {
+      self.v.iter().take(3).ne(other.v.iter().take(3))
    }
    
    /// Construct a file handle from bytes
-   pub fn from_vec(src: &[u8]) -> Result<LinuxFileHandle,TryReserveError>
+   ///
+   /// Fails with ```InvalidData``` if ```handle_bytes``` is larger than ```MAX_HANDLE_SZ``` (```HandleError::HandleBytesTooLarge```), if the bytes are shorter than the header (```handle_bytes``` and ```handle_type```), or if ```handle_bytes``` is larger than the payload that follows it (```HandleError::Malformed```); the ```HandleError``` is the inner error of the ```std::io::Error```. Trailing padding after the payload is accepted (and dropped), as written by older versions of ```get_vec()```
+   ///
+   /// ```from_vec(&h.get_vec()?)``` gives a handle with the same words as ```h``` (see ```get_slice()```) for every ```handle_bytes```, whether or not it is a multiple of 4
+   pub fn from_vec(src: &[u8]) -> std::io::Result<LinuxFileHandle>
+   {
+      Ok(Self::from_vec_impl(src)?)
+   }
+
+   /// ```from_vec()``` returning the ```HandleError``` itself, for the constructors that return one
+   fn from_vec_impl(src: &[u8]) -> Result<LinuxFileHandle,HandleError>
    {
+      let handle_bytes = match src.first_chunk::<4>()
+      {
+         Some(h) if src.len() >= 8 => u32::from_ne_bytes(*h) as usize,
+         _ => return Err(HandleError::Malformed("shorter than the file handle header")),
+      };
+      if handle_bytes > MAX_HANDLE_SZ as usize { return Err(HandleError::HandleBytesTooLarge(handle_bytes)); }
+      if handle_bytes > src.len() - 8 { return Err(HandleError::Malformed("handle_bytes exceeds the payload")); }
+      // padding is dropped and the last word is zero-filled, so that the words are the same as those of the handle get_vec() was called on
+      let src = &src[..8 + handle_bytes];
       let mut result = Vec::<u32>::new();
       result.try_reserve(Self::get_aligned_fh_size(src.len()))?;
       for i in src.chunks(4)
@@ -103,79 +567,223 @@ impl LinuxFileHandle
          }
          result.push(u32::from_ne_bytes(arr));
       }
-      Ok(LinuxFileHandle { v : result, mnt_id: -1 })
+      Ok(LinuxFileHandle { v : result, mnt_id: -1, at_flags: None, unique_mnt_id: None })
+   }
+
+   /// Decodes a string produced by ```to_hex()``` (digits of either case) and creates a file-handle from the bytes, see ```from_vec()```
+   ///
+   /// Fails with ```Malformed``` if the string has an odd length or contains anything but hex digits, and with the errors of ```from_vec()``` if the bytes are not a valid handle
+   pub fn from_hex(s: &str) -> Result<LinuxFileHandle,HandleError>
+   {
+      match hex::decode(s)
+      {
+         Some(bytes) => Self::from_vec_impl(&bytes),
+         None => Err(HandleError::Malformed("not a hex string of whole bytes")),
+      }
+   }
+
+   /// Creates a file-handle from its ```handle_type``` and the ```f_handle``` bytes, e.g. as reported separately by other interfaces
+   ///
+   /// ```handle_bytes``` is the length of ```bytes```, which must be at most ```MAX_HANDLE_SZ``` (```HandleBytesTooLarge``` otherwise). The handle has no mount ID
+   pub fn from_raw(handle_type: u32, bytes: &[u8]) -> Result<LinuxFileHandle,HandleError>
+   {
+      if bytes.len() > MAX_HANDLE_SZ as usize { return Err(HandleError::HandleBytesTooLarge(bytes.len())); }
+      let mut v = Vec::<u32>::new();
+      v.try_reserve(2 + bytes.len().div_ceil(4))?;
+      v.extend_from_slice(&[bytes.len() as u32, handle_type]);
+      for chunk in bytes.chunks(4)
+      {
+         let mut word: [u8; 4] = [0; 4];
+         word[..chunk.len()].copy_from_slice(chunk);
+         v.push(u32::from_ne_bytes(word));
+      }
+      Ok(LinuxFileHandle { v, mnt_id: -1, at_flags: None, unique_mnt_id: None })
+   }
+
+   /// Creates a file-handle from the values of ```get_handle_type()``` and ```payload_bytes()```, the same as ```from_raw()```
+   pub fn from_parts(handle_type: u32, payload: &[u8]) -> Result<LinuxFileHandle,HandleError>
+   {
+      Self::from_raw(handle_type, payload)
+   }
+
+   /// Same as ```from_parts()```, but the handle gets the mount ID ```mnt_id``` (as returned by ```get_mnt_id()```), e.g. to restore a handle saved together with its mount ID
+   ///
+   /// Fails with ```HandleBytesTooLarge``` if the payload is larger than ```MAX_HANDLE_SZ```, and with ```Malformed``` if ```mnt_id``` is negative. The mount ID is not checked against the mounts of this host: the caller is responsible for it being meaningful here (mount IDs are reused after a reboot or an unmount, see ```mnt_id_still_matches()```)
+   pub fn from_parts_with_mnt(mnt_id: i32, handle_type: u32, payload: &[u8]) -> Result<LinuxFileHandle,HandleError>
+   {
+      if mnt_id < 0 { return Err(HandleError::Malformed("mount ID is negative")); }
+      let mut fh = Self::from_raw(handle_type, payload)?;
+      fh.mnt_id = mnt_id;
+      Ok(fh)
+   }
+
+   /// Copies a ```struct file_handle``` owned by C code, e.g. the one ```fanotify``` reports inline in ```struct fanotify_event_info_fid``` with ```FAN_REPORT_FID```
+   ///
+   /// ```handle_bytes``` is read first, and a value larger than ```MAX_HANDLE_SZ``` is rejected with ```HandleBytesTooLarge``` before anything else is read. The handle has no mount ID
+   ///
+   /// # Safety
+   ///
+   /// ```ptr``` must point to a readable ```struct file_handle``` (it does not have to be aligned) whose ```f_handle``` array is at least ```handle_bytes``` bytes long (or whose ```handle_bytes``` is larger than ```MAX_HANDLE_SZ```), and the memory must not be modified during the call
+   pub unsafe fn from_file_handle_ptr(ptr: *const std::ffi::c_void) -> Result<LinuxFileHandle,HandleError>
+   {
+      let base = ptr as *const u8;
+      let handle_bytes = unsafe { std::ptr::read_unaligned(base as *const u32) };
+      if handle_bytes > MAX_HANDLE_SZ { return Err(HandleError::HandleBytesTooLarge(Self::get_usize(handle_bytes)?)); }
+      let handle_type = unsafe { std::ptr::read_unaligned(base.add(4) as *const u32) };
+      let payload = unsafe { std::slice::from_raw_parts(base.add(std::mem::size_of::<file_handle>()), handle_bytes as usize) };
+      Self::from_raw(handle_type, payload)
+   }
+
+   /// Copies the bytes returned by ```get_vec()``` into a zero-padded fixed-size array, e.g. to embed the handle into a ```#[repr(C)]``` packet structure, and returns the array along with the number of bytes actually used
+   ///
+   /// Fails with ```BufferTooSmall``` if the handle does not fit into ```N``` bytes
+   pub fn to_array<const N: usize>(&self) -> Result<([u8; N], usize),HandleError>
+   {
+      let needed = self.handle_len();
+      if needed > N { return Err(HandleError::BufferTooSmall { needed, available: N }); }
+      let mut result: [u8; N] = [0; N];
+      let header = self.v.iter().take(2).flat_map(|w| w.to_ne_bytes());
+      for (dst, src) in result.iter_mut().zip(header.chain(self.payload_iter()))
+      {
+         *dst = src;
+      }
+      Ok((result, needed))
+   }
+   
+   /// Creates a file-handle from the first ```len``` bytes of an array filled by ```to_array()```
+   pub fn from_array<const N: usize>(src: &[u8; N], len: usize) -> Result<LinuxFileHandle,HandleError>
+   {
+      match src.get(..len)
+      {
+         Some(bytes) => Self::from_vec_impl(bytes),
+         None => Err(HandleError::Malformed("length exceeds the array")),
+      }
+   }
+
+   /// Packs the words of ```get_slice()``` into ```u64``` words, e.g. for storage in 8-byte aligned memory-mapped regions. Every ```u64``` word holds two ```u32``` words in memory order; if the number of ```u32``` words is odd, the last ```u64``` word is padded with zeroes
+   pub fn to_u64_words(&self) -> Result<Vec<u64>,TryReserveError>
+   {
+      let mut result = Vec::<u64>::new();
+      result.try_reserve(self.v.len().div_ceil(2))?;
+      for pair in self.v.chunks(2)
+      {
+         let mut bytes: [u8; 8] = [0; 8];
+         bytes[..4].copy_from_slice(&pair[0].to_ne_bytes());
+         if let Some(second) = pair.get(1) { bytes[4..].copy_from_slice(&second.to_ne_bytes()); }
+         result.push(u64::from_ne_bytes(bytes));
+      }
+      Ok(result)
+   }
+   
+   /// Unpacks a handle packed by ```to_u64_words()```, ```mnt_id``` is the mount ID to attach to it (if known)
+   ///
+   /// The ```u32``` and ```u64``` representations are interconvertible, but differ in trailing alignment: the words beyond those covering ```handle_bytes``` are dropped, so that the padding added by ```to_u64_words()``` does not become part of the handle
+   ///
+   /// The header is checked just like by ```from_vec()```: fails with ```HandleBytesTooLarge``` if ```handle_bytes``` is larger than ```MAX_HANDLE_SZ```, and with ```Malformed``` if there are no words or if ```handle_bytes``` is larger than the payload that follows the header
+   pub fn from_u64_words(src: &[u64], mnt_id: Option<i32>) -> Result<LinuxFileHandle,HandleError>
+   {
+      let handle_bytes = match src.first()
+      {
+         Some(w) => {
+            let bytes = w.to_ne_bytes();
+            u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+         },
+         None => return Err(HandleError::Malformed("shorter than the file handle header")),
+      };
+      if handle_bytes > MAX_HANDLE_SZ as usize { return Err(HandleError::HandleBytesTooLarge(handle_bytes)); }
+      if handle_bytes > (src.len() - 1) * 8 { return Err(HandleError::Malformed("handle_bytes exceeds the payload")); }
+      let mut result = Vec::<u32>::new();
+      result.try_reserve(src.len() * 2)?;
+      for w in src
+      {
+         let bytes = w.to_ne_bytes();
+         result.push(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+         result.push(u32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]));
+      }
+      result.truncate(2 + Self::get_aligned_fh_size(handle_bytes));
+      Ok(LinuxFileHandle { v: result, mnt_id: mnt_id.unwrap_or(-1), at_flags: None, unique_mnt_id: None })
    }
 
    #[inline(always)]
-   fn get_signed(s: u32) -> std::io::Result<i32>
+   fn get_signed(s: u32) -> Result<i32,HandleError>
    {
       match s.try_into()
       {
          Ok(f) => Ok(f),
-         Err(_) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "conversion error")),
+         Err(_) => Err(HandleError::Conversion),
       }
    }
    
    #[inline(always)]
    fn get_aligned_fh_size(s: usize) -> usize
    {
-      let align = 4 - (s % 4);
-      let aligned_size = s + align;
-      aligned_size/4
+      s.div_ceil(4)
    }
    
    #[inline(always)]
-   fn get_usize(s: u32) -> std::io::Result<usize>
+   fn get_usize(s: u32) -> Result<usize,HandleError>
    {
       match s.try_into()
       {
          Ok(f) => Ok(f),
-         Err(_) => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "conversion error")),
+         Err(_) => Err(HandleError::Conversion),
       }
    }
 
-   #[inline(always)]
-   #[allow(unused_assignments)]
-   fn obtain_impl(dirfd: Option<BorrowedFd<'_>>, path: &str, flags: std::os::raw::c_int) -> std::io::Result<LinuxFileHandle>
+   /// Iterates over the bytes of the opaque payload (```f_handle```), stopping after ```handle_bytes``` of them
+   fn payload_iter(&self) -> impl Iterator<Item = u8> + '_
+   {
+      let handle_bytes = self.v.first().copied().unwrap_or(0) as usize;
+      self.v.iter().skip(2).flat_map(|w| w.to_ne_bytes()).take(handle_bytes)
+   }
+
+   /// Rejects paths that contain a NUL byte: the kernel would see a shorter path than intended and resolve a different file
+   fn check_no_nul(path: &[u8]) -> std::io::Result<()>
+   {
+      if path.contains(&0) { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "path contains a NUL byte")); }
+      Ok(())
+   }
+
+   /// Builds the NUL-terminated copy of a path passed to the system calls, see ```check_no_nul()```
+   fn c_path(path: &[u8]) -> std::io::Result<Vec<u8>>
+   {
+      Self::check_no_nul(path)?;
+      let mut path_v = Vec::<u8>::new();
+      path_v.try_reserve(path.len() + 1)?;
+      path_v.extend_from_slice(path);
+      path_v.push(0);
+      Ok(path_v)
+   }
+   
+   /// Opens a file with ```O_PATH```, without following a trailing symbolic link (just like ```obtain()``` does not)
+   fn open_path(dirfd: Option<BorrowedFd<'_>>, path: &[u8]) -> std::io::Result<OwnedFd>
    {
-      let mut fdclone: Option<OwnedFd> = None;
       let d_fd = match dirfd
       {
-         Some(fd) => {
-            fdclone = Some(fd.try_clone_to_owned()?);
-            fdclone.as_ref().unwrap().as_raw_fd()
-         },
+         Some(fd) => fd.as_raw_fd(),
          None => AT_FDCWD,
       };
-      let mut mnt_id: i32 = 0;
-      let mut fh = Vec::<u32>::new();
-      fh.try_reserve(2)?;
-      fh.extend_from_slice(&[0, 0]);
-      let mut path_v = Vec::<u8>::new();
-      path_v.try_reserve(path.len() + 1)?;
-      path_v.extend_from_slice(path.as_bytes());
-      path_v.push(0);
-      let _ = unsafe { name_to_handle_at(d_fd, path_v.as_ptr() as *const i8, fh.as_mut_ptr() as *mut file_handle, &mut mnt_id as *mut i32, flags) };
-      let first_err = std::io::Error::last_os_error(); // first call to name_to_handle_at() should normally fail with EOVERFLOW, checking if it's indeed the case
-      if let Some(err) = first_err.raw_os_error()
+      let path_v = Self::c_path(path)?;
+      let r = unsafe { openat(d_fd, path_v.as_ptr() as *const i8, Self::get_signed(O_PATH | O_NOFOLLOW | O_CLOEXEC)?) };
+      if r >= 0
       {
-         if err != Self::get_signed(EOVERFLOW)? { return Err(first_err); } // not EOVERFLOW, that means the second call to name_to_handle_at() is pointless
+         unsafe { Ok(OwnedFd::from_raw_fd(r)) }
       }
       else
       {
-         return Err(first_err); // something very unexpected
+         Err(std::io::Error::last_os_error())
       }
-      //let handle_bytes: [u8; 4] = [fh[0], fh[1], fh[2], fh[3]];
-      let fh_size = fh[0]; // u32::from_ne_bytes(handle_bytes);
-      fh.try_reserve(Self::get_aligned_fh_size(Self::get_usize(fh_size)?))?;
-      while fh.len() < fh.capacity()
-      {
-         fh.push(0);
-      }
-      let r = unsafe { name_to_handle_at(d_fd, path_v.as_ptr() as *const i8, fh.as_mut_ptr() as *mut file_handle, &mut mnt_id as *mut i32, flags) };
-      if r == 0
+   }
+   
+   /// Same as ```open_path()```, but resolves the path with ```openat2()``` and ```RESOLVE_BENEATH```, which fails with ```EXDEV``` if the path (including ```..``` components and symbolic links) would leave ```dirfd```
+   fn open_path_beneath(dirfd: BorrowedFd<'_>, path: &[u8]) -> std::io::Result<OwnedFd>
+   {
+      let path_v = Self::c_path(path)?;
+      let mut how = open_how { flags: u64::from(O_PATH | O_NOFOLLOW | O_CLOEXEC), mode: 0, resolve: u64::from(RESOLVE_BENEATH) };
+      let r = unsafe { syscall(i64::from(SYS_openat2), dirfd.as_raw_fd(), path_v.as_ptr() as *const i8, &mut how as *mut open_how, std::mem::size_of::<open_how>()) };
+      if r >= 0
       {
-         Ok(LinuxFileHandle { v: fh, mnt_id: mnt_id })
+         unsafe { Ok(OwnedFd::from_raw_fd(r as i32)) }
       }
       else
       {
@@ -183,46 +791,567 @@ impl LinuxFileHandle
       }
    }
    
+   fn fstat_fd(fd: BorrowedFd<'_>) -> std::io::Result<stat>
+   {
+      let mut st = std::mem::MaybeUninit::<stat>::uninit();
+      if unsafe { fstat(fd.as_raw_fd(), st.as_mut_ptr()) } != 0 { return Err(std::io::Error::last_os_error()); }
+      Ok(unsafe { st.assume_init() })
+   }
+
+   #[inline(always)]
+   fn obtain_impl(dirfd: Option<BorrowedFd<'_>>, path: &[u8], flags: AtFlags) -> std::io::Result<LinuxFileHandle>
+   {
+      Self::obtain_impl_hinted(dirfd, path, flags, 0)
+   }
+   
+   /// Same as ```obtain_impl()```, but the first call to ```name_to_handle_at()``` is made with room for ```initial_words``` words of payload, so that it succeeds right away if the hint is large enough
+   fn obtain_impl_hinted(dirfd: Option<BorrowedFd<'_>>, path: &[u8], flags: AtFlags, initial_words: usize) -> std::io::Result<LinuxFileHandle>
+   {
+      let d_fd = match dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      let raw_flags = Self::get_signed(flags.bits())?;
+      let mut mnt_id: i32 = 0;
+      let path_v = Self::c_path(path)?;
+      let fh = Self::call_sized(initial_words, |buf| {
+         if unsafe { name_to_handle_at(d_fd, path_v.as_ptr() as *const i8, buf.as_mut_ptr() as *mut file_handle, &mut mnt_id as *mut i32, raw_flags) } == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) }
+      })?;
+      let result = LinuxFileHandle { v: fh, mnt_id, at_flags: Some(flags), unique_mnt_id: None };
+      // handles obtained with AT_HANDLE_FID may not be openable
+      #[cfg(feature = "verify-on-obtain")]
+      if !flags.contains(AtFlags::AT_HANDLE_FID) { result.verify_reopen(d_fd, &path_v, flags)?; }
+      Ok(result)
+   }
+   
+   /// Makes ```call``` (a call to ```name_to_handle_at()``` with the given buffer) until the buffer is large enough: the first call is made with room for ```initial_words``` words of payload, and every ```EOVERFLOW``` grows the buffer to the size the kernel reported
+   ///
+   /// The size can change between two calls (e.g. if the file is replaced by one on a file-system with larger handles), so it makes at most ```MAX_SIZING_ATTEMPTS``` calls before giving up
+   ///
+   /// With the ```no-size-probe``` feature, the first call is always made with room for ```MAX_HANDLE_SZ``` bytes, whatever ```initial_words``` is
+   fn call_sized<F: FnMut(&mut [u32]) -> std::io::Result<()>>(initial_words: usize, mut call: F) -> std::io::Result<Vec<u32>>
+   {
+      let initial_words = if cfg!(feature = "no-size-probe") { MAX_HANDLE_WORDS - 2 } else { initial_words.min(MAX_HANDLE_WORDS - 2) };
+      let mut fh = Vec::<u32>::new();
+      fh.try_reserve(2 + initial_words)?;
+      fh.resize(2 + initial_words, 0);
+      fh[0] = (initial_words * 4) as u32;
+      for _ in 0..MAX_SIZING_ATTEMPTS
+      {
+         let err = match call(&mut fh)
+         {
+            Ok(()) => {
+               // dropping the words the handle does not use, if the buffer was larger than needed
+               fh.truncate(2 + Self::get_usize(fh[0])?.div_ceil(4));
+               return Ok(fh);
+            },
+            Err(e) => e,
+         };
+         if err.raw_os_error() != Some(Self::get_signed(EOVERFLOW)?) { return Err(err); } // only EOVERFLOW means that a larger buffer can help
+         // the kernel has stored the size it needs in handle_bytes
+         let fh_words = 2 + Self::get_aligned_fh_size(Self::get_usize(fh[0])?);
+         fh.try_reserve(fh_words.saturating_sub(fh.len()))?;
+         fh.resize(fh_words, 0);
+      }
+      Err(std::io::Error::other("size of the file handle kept changing between calls to name_to_handle_at()"))
+   }
+   
+   /// Checks that a just-obtained handle opens back to the file it was obtained from (```d_fd``` and ```path_v``` are what ```name_to_handle_at()``` was called with)
+   #[cfg(feature = "verify-on-obtain")]
+   fn verify_reopen(&self, d_fd: i32, path_v: &[u8], flags: AtFlags) -> std::io::Result<()>
+   {
+      use std::os::unix::fs::OpenOptionsExt;
+      let target = if path_v.len() <= 1
+      {
+         None // AT_EMPTY_PATH, the handle is for d_fd itself
+      }
+      else
+      {
+         let nofollow = if flags.contains(AtFlags::AT_SYMLINK_FOLLOW) { 0 } else { O_NOFOLLOW };
+         let r = unsafe { openat(d_fd, path_v.as_ptr() as *const i8, Self::get_signed(O_PATH | O_CLOEXEC | nofollow)?) };
+         if r < 0 { return Err(std::io::Error::last_os_error()); }
+         Some(unsafe { OwnedFd::from_raw_fd(r) })
+      };
+      let target_fd = target.as_ref().map_or(d_fd, |f| f.as_raw_fd());
+      let mut expected = std::mem::MaybeUninit::<stat>::uninit();
+      if unsafe { fstatat(target_fd, c"".as_ptr(), expected.as_mut_ptr(), Self::get_signed(AT_EMPTY_PATH)?) } != 0 { return Err(std::io::Error::last_os_error()); }
+      let expected = unsafe { expected.assume_init() };
+      // open_by_handle_at() does not accept O_PATH descriptors as mount_fd: regular files and directories are reopened for reading (which has no side effects), anything else is verified through its mount point
+      let mnt_fd: OwnedFd = match expected.st_mode & S_IFMT
+      {
+         S_IFREG | S_IFDIR => {
+            let reopen_path = if target_fd == AT_FDCWD { String::from(".") } else { format!("/proc/self/fd/{}", target_fd) };
+            std::fs::File::options().read(true).custom_flags(Self::get_signed(O_NONBLOCK | O_NOCTTY)?).open(reopen_path)?.into()
+         },
+         _ => {
+            let table = MountTable::load()?;
+            let mount_point = self.get_mnt_id().and_then(|m| table.mount_point(m)).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "mount of the obtained file handle is not in the mount table"))?;
+            std::fs::File::open(mount_point)?.into()
+         },
+      };
+      // open_by_handle_at() does not modify the handle
+      let r = unsafe { open_by_handle_at(mnt_fd.as_fd().as_raw_fd(), self.v.as_ptr() as *const file_handle as *mut file_handle, Self::get_signed(O_PATH | O_CLOEXEC)?) };
+      if r < 0 { return Err(Self::open_error()); }
+      let reopened = unsafe { OwnedFd::from_raw_fd(r) };
+      let actual = Self::fstat_fd(reopened.as_fd())?;
+      if (actual.st_dev, actual.st_ino) != (expected.st_dev, expected.st_ino) { return Err(std::io::Error::other("obtained file handle opens a different file")); }
+      Ok(())
+   }
+   
    /// Retrieve a file handle for the given file relative to dirfd (if dirfd is None, then the current directory is used)```
-   pub fn obtain(dirfd: Option<BorrowedFd<'_>>, path: &str) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl(dirfd, path, 0) }
+   ///
+   /// Like all ```*at()``` system calls, ```name_to_handle_at()``` ignores dirfd if the path is absolute. Use ```obtain_with_policy()``` if that must not happen
+   ///
+   /// The path is passed to the kernel as a byte string, so it does not need to be valid UTF-8. A path containing a NUL byte is rejected with ```InvalidInput```
+   pub fn obtain(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle> { Self::obtain_with_flags(dirfd, path, AtFlags::empty()) }
+   
+   /// Same as ```obtain()```, but ```name_to_handle_at()``` is called with ```flags```, in any combination (e.g. ```AT_EMPTY_PATH | AT_SYMLINK_FOLLOW```); ```obtain()```, ```obtain_follow()``` and ```obtain_fd()``` are shorthands for the common ones
+   ///
+   /// With ```AT_EMPTY_PATH``` and an empty path, the handle is obtained for dirfd itself (which can be any file, not only a directory), and ```AT_SYMLINK_FOLLOW``` has no effect; with a path that is not empty, ```AT_EMPTY_PATH``` has no effect
+   pub fn obtain_with_flags(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, flags: AtFlags) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl(dirfd, path.as_ref().as_os_str().as_bytes(), flags) }
+   
+   /// Retrieve a file handle for ```rel```, resolved beneath the directory ```root_path``` (e.g. the ```RootDirectory=``` of a service), without following a trailing symbolic link
+   ///
+   /// ```root_path``` is opened as a directory, then ```rel``` is resolved with ```openat2()``` and ```RESOLVE_BENEATH```: absolute paths, and ```..``` components or symbolic links that would leave the root, make it fail with ```EXDEV``` (rather than being resolved as in a ```chroot()```). Requires Linux 5.6 or later
+   pub fn obtain_under_root(root_path: impl AsRef<Path>, rel: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle>
+   {
+      let root_v = Self::c_path(root_path.as_ref().as_os_str().as_bytes())?;
+      let r = unsafe { openat(AT_FDCWD, root_v.as_ptr() as *const i8, Self::get_signed(O_PATH | O_DIRECTORY | O_CLOEXEC)?) };
+      if r < 0 { return Err(std::io::Error::last_os_error()); }
+      let root = unsafe { OwnedFd::from_raw_fd(r) };
+      let fd = Self::open_path_beneath(root.as_fd(), rel.as_ref().as_os_str().as_bytes())?;
+      Self::obtain_fd(Some(fd.as_fd()))
+   }
+   
+   /// Same as ```obtain()```, but only obtains the handle if the calling process may access the file with ```mode```, and returns None otherwise, e.g. so that a server does not hand out handles of files its client cannot access
+   ///
+   /// ```name_to_handle_at()``` itself does not check permissions on the file. The access is checked first with ```faccessat()```, using the effective user and group IDs (```AT_EACCESS```) and without following a trailing symbolic link. ```EACCES``` and ```EROFS``` (for ```W_OK``` on a read-only file-system) give None, any other error is returned. The check and the obtaining of the handle are separate steps: if the file is replaced or its permissions change in between, the handle can be obtained for a file that fails the check
+   pub fn obtain_if_accessible(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, mode: AccessMode) -> std::io::Result<Option<LinuxFileHandle>>
+   {
+      let path = path.as_ref().as_os_str().as_bytes();
+      let d_fd = match dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      let path_v = Self::c_path(path)?;
+      if unsafe { faccessat(d_fd, path_v.as_ptr() as *const i8, Self::get_signed(mode.bits())?, Self::get_signed(AT_EACCESS | AT_SYMLINK_NOFOLLOW)?) } != 0
+      {
+         let err = std::io::Error::last_os_error();
+         return match err.raw_os_error().and_then(|e| u32::try_from(e).ok())
+         {
+            Some(EACCES) | Some(EROFS) => Ok(None),
+            _ => Err(err),
+         };
+      }
+      Ok(Some(Self::obtain_impl(dirfd, path, AtFlags::empty())?))
+   }
+   
+   /// Retrieve a file handle for the executable of the calling process, e.g. to detect that the binary on disk has been replaced by an upgrade (the stored handle no longer opens, or ```obtain_with_content_hash()``` returns a different token)
+   ///
+   /// ```/proc/self/exe``` is opened with ```O_PATH```, which follows the magic link to the file the kernel actually executed instead of resolving its target path, so this works even if the binary has been moved, replaced or deleted since
+   pub fn obtain_self_exe() -> std::io::Result<LinuxFileHandle>
+   {
+      let r = unsafe { openat(AT_FDCWD, c"/proc/self/exe".as_ptr(), Self::get_signed(O_PATH | O_CLOEXEC)?) };
+      if r < 0 { return Err(std::io::Error::last_os_error()); }
+      let exe = unsafe { OwnedFd::from_raw_fd(r) };
+      Self::obtain_fd(Some(exe.as_fd()))
+   }
+   
+   /// Same as ```obtain()```, but retries up to ```max_retries``` times (0 disables retrying) if ```name_to_handle_at()``` fails with a transient error, waiting 1 ms before the first retry and twice as long before every next one (at most 100 ms)
+   ///
+   /// The transient errors are ```ENOMEM```, which the kernel can return under memory pressure, and ```EAGAIN```. Any other error is returned right away, and a transient error that persists after the last retry is returned as well. A failure to allocate memory for the handle in the process itself is not retried
+   pub fn obtain_with_retry(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, max_retries: u32) -> std::io::Result<LinuxFileHandle>
+   {
+      let path = path.as_ref().as_os_str().as_bytes();
+      let mut backoff = RETRY_INITIAL_BACKOFF;
+      let mut retries: u32 = 0;
+      loop
+      {
+         let err = match Self::obtain_impl(dirfd, path, AtFlags::empty())
+         {
+            Ok(fh) => return Ok(fh),
+            Err(e) => e,
+         };
+         let transient = matches!(err.raw_os_error().and_then(|e| u32::try_from(e).ok()), Some(ENOMEM) | Some(EAGAIN));
+         if !transient || retries >= max_retries { return Err(err); }
+         std::thread::sleep(backoff);
+         backoff = (backoff * 2).min(RETRY_MAX_BACKOFF);
+         retries += 1;
+      }
+   }
+   
+   /// Same as ```obtain()```, but makes room for ```initial_words``` words of payload (at most ```MAX_HANDLE_SZ``` bytes) in the first call to ```name_to_handle_at()```, which saves the second call if the size of the handles on the file-system is known in advance
+   ///
+   /// The stored handle is trimmed to the words covering ```handle_bytes```, so an over-generous hint does not leave trailing words in it
+   pub fn obtain_with_initial_words(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, initial_words: usize) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl_hinted(dirfd, path.as_ref().as_os_str().as_bytes(), AtFlags::empty(), initial_words) }
+   
+   /// Same as ```obtain()```, but the first call to ```name_to_handle_at()``` is made with room for ```MAX_HANDLE_SZ``` bytes of payload, so that plenty of handles are obtained with a single call instead of two (on ext4 or xfs, for instance, ```obtain()``` always makes the first call fail with ```EOVERFLOW``` to learn the size). On ext4, this takes about half the time of ```obtain()```
+   ///
+   /// The stored handle is trimmed to the words covering ```handle_bytes```, just like with ```obtain_with_initial_words()```. Should the kernel still return ```EOVERFLOW```, the buffer is grown like in ```obtain()```
+   pub fn obtain_fast(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl_hinted(dirfd, path.as_ref().as_os_str().as_bytes(), AtFlags::empty(), MAX_HANDLE_WORDS - 2) }
+   
+   /// Same as ```obtain()```, but decides with ```policy``` what happens if dirfd is supplied and the path is absolute (and would therefore be resolved from the root directory, ignoring dirfd)
+   ///
+   /// Please note that ```AbsolutePathPolicy::StripRoot``` only makes the path relative, the ```..``` components can still lead outside of dirfd
+   pub fn obtain_with_policy(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, policy: AbsolutePathPolicy) -> std::io::Result<LinuxFileHandle>
+   {
+      let path = path.as_ref().as_os_str().as_bytes();
+      if dirfd.is_none() || !path.starts_with(b"/") { return Self::obtain_impl(dirfd, path, AtFlags::empty()); }
+      match policy
+      {
+         AbsolutePathPolicy::Allow => Self::obtain_impl(dirfd, path, AtFlags::empty()),
+         AbsolutePathPolicy::Reject => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "absolute path would ignore the directory file descriptor")),
+         AbsolutePathPolicy::StripRoot => {
+            let relative = &path[path.iter().take_while(|&&b| b == b'/').count()..];
+            Self::obtain_impl(dirfd, if relative.is_empty() { b"." } else { relative }, AtFlags::empty())
+         },
+      }
+   }
+   
+   /// Same as ```obtain()```, but also returns ```handle_bytes```, the size of the opaque part of the handle reported by the kernel
+   pub fn obtain_sized_info(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<(LinuxFileHandle, usize)>
+   {
+      let fh = Self::obtain_impl(dirfd, path.as_ref().as_os_str().as_bytes(), AtFlags::empty())?;
+      let handle_bytes = Self::get_usize(fh.v[0])?;
+      Ok((fh, handle_bytes))
+   }
+   
+   /// Same as ```obtain()```, but also returns a change token of the file, so that backup and sync tools can later tell whether the file has changed since the handle was taken
+   ///
+   /// The file is opened with ```O_PATH``` once, and the handle and the token are both computed from that file descriptor (reopened read-only for ```ChangeToken::Content```), so they always belong to the same inode. The token is a 64-bit FNV-1a hash: ```ChangeToken::Content``` hashes the entire contents of the file, which is exact but expensive for large files (and fails for anything that cannot be read, like directories), while ```ChangeToken::Metadata``` only hashes the size, modification time and inode number, which is cheap but approximate
+   pub fn obtain_with_content_hash(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>, token: ChangeToken) -> std::io::Result<(LinuxFileHandle, u64)>
+   {
+      let fd = Self::open_path(dirfd, path.as_ref().as_os_str().as_bytes())?;
+      let fh = Self::obtain_fd(Some(fd.as_fd()))?;
+      let mut hash = FNV_OFFSET_BASIS;
+      match token
+      {
+         ChangeToken::Content => {
+            let mut file = std::fs::File::open(format!("/proc/self/fd/{}", fd.as_raw_fd()))?;
+            let mut buf: [u8; 8192] = [0; 8192];
+            loop
+            {
+               let n = match file.read(&mut buf)
+               {
+                  Ok(0) => break,
+                  Ok(n) => n,
+                  Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                  Err(e) => return Err(e),
+               };
+               hash = fnv1a(hash, &buf[..n]);
+            }
+         },
+         ChangeToken::Metadata => {
+            let st = Self::fstat_fd(fd.as_fd())?;
+            for field in [st.st_size as u64, st.st_mtim.tv_sec as u64, st.st_mtim.tv_nsec as u64, st.st_ino]
+            {
+               hash = fnv1a(hash, &field.to_le_bytes());
+            }
+         },
+      }
+      Ok((fh, hash))
+   }
+   
+   /// Retrieve a file handle for the given file as seen in another mount namespace. ```mntns_fd``` is a file descriptor for that namespace, e.g. ```/proc/<pid>/ns/mnt``` of a process in it
+   ///
+   /// ```setns()``` changes the mount namespace of the entire calling thread, so the handle is obtained on a short-lived dedicated thread and the namespace of the caller is left intact. Relative paths are resolved from the root directory of the namespace. Entering a mount namespace requires ```CAP_SYS_ADMIN``` and ```CAP_SYS_CHROOT```
+   pub fn obtain_in_mntns(mntns_fd: BorrowedFd<'_>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle>
+   {
+      let path = path.as_ref().as_os_str().as_bytes();
+      std::thread::scope(|scope| {
+         let worker = std::thread::Builder::new().spawn_scoped(scope, || {
+            // threads share the root and current directories, setns() refuses to change the mount namespace if they are shared
+            if unsafe { unshare(Self::get_signed(CLONE_FS)?) } != 0 { return Err(std::io::Error::last_os_error()); }
+            if unsafe { setns(mntns_fd.as_raw_fd(), Self::get_signed(CLONE_NEWNS)?) } != 0 { return Err(std::io::Error::last_os_error()); }
+            Self::obtain_impl(None, path, AtFlags::empty())
+         })?;
+         worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+      })
+   }
+   
+   /// Best-effort recovery of a lost handle from a known inode number: walks the tree under ```root``` at most ```max_depth``` levels deep (0 only checks ```root``` itself), looking for an entry with the inode number ```ino``` on the file-system of ```root```, and obtains its handle
+   ///
+   /// Returns None if there is no such entry within the depth limit. Symbolic links are not followed, unreadable directories are skipped, and so are entries that disappear during the walk (even the matching one, if it is renamed or unlinked before its handle is obtained, in which case the walk goes on). Every entry gets ```lstat()```-ed, so this is potentially very expensive: it is a disaster-recovery tool, not something for hot paths
+   pub fn obtain_by_inode(root: BorrowedFd<'_>, ino: u64, max_depth: usize) -> std::io::Result<Option<LinuxFileHandle>>
+   {
+      let root_path = PathBuf::from(format!("/proc/self/fd/{}", root.as_raw_fd()));
+      let root_meta = std::fs::metadata(&root_path)?;
+      if root_meta.ino() == ino { return Self::obtain_fd(Some(root)).map(Some); }
+      let mut pending = vec![(PathBuf::new(), 0usize)];
+      while let Some((dir, depth)) = pending.pop()
+      {
+         if depth >= max_depth { continue; }
+         let entries = match std::fs::read_dir(root_path.join(&dir))
+         {
+            Ok(e) => e,
+            Err(_) => continue,
+         };
+         for entry in entries.flatten()
+         {
+            // DirEntry::metadata() does not follow symbolic links
+            let meta = match entry.metadata()
+            {
+               Ok(m) => m,
+               Err(_) => continue,
+            };
+            if meta.dev() != root_meta.dev() { continue; } // inode numbers are only unique within a file-system
+            let relative = dir.join(entry.file_name());
+            if meta.ino() == ino
+            {
+               match Self::obtain_impl(Some(root), relative.as_os_str().as_bytes(), AtFlags::empty())
+               {
+                  Ok(fh) => return Ok(Some(fh)),
+                  // renamed or unlinked since it was read, just like when lstat() fails
+                  Err(e) if e.raw_os_error() == Some(Self::get_signed(ENOENT)?) => continue,
+                  Err(e) => return Err(e),
+               }
+            }
+            if meta.is_dir() { pending.push((relative, depth + 1)); }
+         }
+      }
+      Ok(None)
+   }
+   
+   /// Same as ```obtain()```, but also returns the link count of the file, e.g. for an NFS server deciding how to track a file that may get removed while in use
+   ///
+   /// The file is opened with ```O_PATH``` once, and both the handle and the link count are retrieved from that file descriptor, so they always belong to the same inode. The link count can still change right after it has been read
+   pub fn obtain_with_nlink(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<(LinuxFileHandle, u64)>
+   {
+      let fd = Self::open_path(dirfd, path.as_ref().as_os_str().as_bytes())?;
+      let fh = Self::obtain_fd(Some(fd.as_fd()))?;
+      #[allow(clippy::useless_conversion)] // nlink_t is 32-bit on some architectures
+      let nlink = u64::from(Self::fstat_fd(fd.as_fd())?.st_nlink);
+      Ok((fh, nlink))
+   }
+   
+   /// Same as ```obtain()```, but asks the kernel for the 64-bit unique mount ID (```AT_HANDLE_MNT_ID_UNIQUE```, Linux 6.12 or later), see ```get_unique_mnt_id()```. It can be matched with ```statx()``` and ```STATX_MNT_ID_UNIQUE```, or with ```listmount()```
+   ///
+   /// The kernel returns either kind of mount ID, not both, so the handle has no ```get_mnt_id()```. ```AT_HANDLE_MNT_ID_UNIQUE``` only changes the mount ID the kernel returns, not the handle, so it is not part of ```AtFlags```: ```get_at_flags()``` returns no flags, and whether it was used is only recorded by ```get_unique_mnt_id()```. Older kernels reject the flag with ```EINVAL```: the handle is then obtained like with ```obtain()```, with the 32-bit mount ID, and ```get_unique_mnt_id()``` returns None
+   pub fn obtain_with_unique_mnt_id(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle>
+   {
+      Self::obtain_unique_impl(dirfd, path.as_ref().as_os_str().as_bytes(), AT_HANDLE_MNT_ID_UNIQUE)
+   }
+
+   /// ```obtain_with_unique_mnt_id()``` with the raw flags to pass, so that the fallback can be tested with flags the kernel rejects
+   fn obtain_unique_impl(dirfd: Option<BorrowedFd<'_>>, path: &[u8], unique_flags: u32) -> std::io::Result<LinuxFileHandle>
+   {
+      let d_fd = match dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      let raw_flags = Self::get_signed(unique_flags)?;
+      let mut unique_mnt_id: u64 = 0;
+      let path_v = Self::c_path(path)?;
+      let fh = Self::call_sized(0, |buf| {
+         // with AT_HANDLE_MNT_ID_UNIQUE, the kernel writes a u64 instead of an int
+         if unsafe { name_to_handle_at(d_fd, path_v.as_ptr() as *const i8, buf.as_mut_ptr() as *mut file_handle, &mut unique_mnt_id as *mut u64 as *mut i32, raw_flags) } == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) }
+      });
+      match fh
+      {
+         Ok(v) => {
+            let result = LinuxFileHandle { v, mnt_id: -1, at_flags: Some(AtFlags::empty()), unique_mnt_id: Some(unique_mnt_id) };
+            // the unique mount ID flag does not change what the path resolves to
+            #[cfg(feature = "verify-on-obtain")]
+            result.verify_reopen(d_fd, &path_v, AtFlags::empty())?;
+            Ok(result)
+         },
+         Err(e) if e.raw_os_error() == Some(Self::get_signed(EINVAL)?) => Self::obtain_impl(dirfd, path, AtFlags::empty()),
+         Err(e) => Err(e),
+      }
+   }
+   
+   /// Same as ```obtain()```, but passes ```AT_HANDLE_FID``` (Linux 6.5 or later), which asks for a handle that identifies the file rather than one that can be opened, e.g. to match the handles ```fanotify``` reports with ```FAN_REPORT_FID```
+   ///
+   /// Such handles can be obtained on file-systems that cannot decode handles (and so cannot be exported over NFS), but ```open_by_handle()``` may fail for them: they are only meant to be compared with ```==``` or ```same_file()```, and ```is_openable()``` returns ```Some(false)``` for them. Older kernels reject the flag with ```EINVAL```, which is returned as ```HandleError::FlagUnsupported``` (with ```ErrorKind::Unsupported```), so that callers can fall back to ```obtain()```
+   pub fn obtain_fid(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle>
+   {
+      let d_fd = match dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      let raw_flags = Self::get_signed(AT_HANDLE_FID)?;
+      let mut mnt_id: i32 = 0;
+      let path_v = Self::c_path(path.as_ref().as_os_str().as_bytes())?;
+      let fh = Self::call_sized(0, |buf| {
+         if unsafe { name_to_handle_at(d_fd, path_v.as_ptr() as *const i8, buf.as_mut_ptr() as *mut file_handle, &mut mnt_id as *mut i32, raw_flags) } == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) }
+      });
+      // the handle may not be openable, so verify-on-obtain does not apply
+      match fh
+      {
+         Ok(v) => Ok(LinuxFileHandle { v, mnt_id, at_flags: Some(AtFlags::AT_HANDLE_FID), unique_mnt_id: None }),
+         Err(e) if e.raw_os_error() == Some(Self::get_signed(EINVAL)?) => Err(HandleError::FlagUnsupported("AT_HANDLE_FID").into()),
+         Err(e) => Err(e),
+      }
+   }
    
    /// Retrieve a file handle for the given file relative to dirfd (if dirfd is None, then the current directory is used), dereferencing the symbolic links```
-   pub fn obtain_follow(dirfd: Option<BorrowedFd<'_>>, path: &str) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl(dirfd, path, Self::get_signed(AT_SYMLINK_FOLLOW)?) }
+   ///
+   /// Just like with ```obtain()```, the path does not need to be valid UTF-8
+   pub fn obtain_follow(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle> { Self::obtain_with_flags(dirfd, path, AtFlags::AT_SYMLINK_FOLLOW) }
+   
+   /// Retrieve a file handle for the root directory of a bind mount, ```bind_fd``` being a file descriptor for that directory (e.g. its mount point opened with ```O_PATH```)
+   ///
+   /// The handle refers to the bind-mounted directory itself, i.e. to the subtree root on the underlying file-system, not to the root of that file-system (which is what resolving the mount point from its parent might suggest), and carries the mount ID of the bind mount. File handles only identify an inode, not a mount: the handle can be opened with ```mnt_fd``` on any mount of the same file-system, and ```open_by_handle_at()``` does not check that the result is visible below that mount
+   pub fn obtain_bind_root(bind_fd: BorrowedFd<'_>) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl(Some(bind_fd), b"", AtFlags::AT_EMPTY_PATH) }
    
    /// Retrieve a file handle for the file represented by a file descriptor
-   pub fn obtain_fd(fd: Option<BorrowedFd<'_>>) -> std::io::Result<LinuxFileHandle> { Self::obtain_impl(fd, "", Self::get_signed(AT_EMPTY_PATH)?) }
+   ///
+   /// If the descriptor refers to a socket, a pipe or an anonymous inode (see ```is_handleable_fd()```), the error wraps ```HandleError::NotHandleable``` (its kind is ```Unsupported```), rather than being a bare ```EOPNOTSUPP```
+   pub fn obtain_fd(fd: Option<BorrowedFd<'_>>) -> std::io::Result<LinuxFileHandle>
+   {
+      let err = match Self::obtain_with_flags(fd, "", AtFlags::AT_EMPTY_PATH)
+      {
+         Ok(fh) => return Ok(fh),
+         Err(e) => e,
+      };
+      if let (Some(fd), Some(EOPNOTSUPP)) = (fd, err.raw_os_error().and_then(|e| u32::try_from(e).ok()))
+      {
+         if let Ok(Some(kind)) = pseudo_fs_kind(fd) { return Err(HandleError::NotHandleable(kind).into()); }
+      }
+      Err(err)
+   }
    
    /// Opens a file referred to by the file handle. ```mnt_fd``` should be a file descriptor for any file on the filesystem of the target file. ```flags``` is file opening flags, similar to those in ```openat()```
    /// 
    /// Please note that this function requires superuser privileges, and may not be available in containers due to security restrictions.
    ///
+   /// If it fails with ```EPERM``` while ```CAP_DAC_READ_SEARCH``` is not effective, the error is ```HandleError::MissingCapability``` (with ```ErrorKind::PermissionDenied``` and the ```EPERM``` as its source), so that a missing privilege is told apart from a handle the kernel refuses, see ```has_open_capability()```
+   ///
+   /// ```open_by_handle_at()``` has no ```mode``` argument: ```O_CREAT``` is rejected, since a handle always refers to an existing file, and the unnamed file created with ```O_TMPFILE``` (on the handle of a directory) has no permission bits, see ```open_by_handle_with_mode()```. Contradicting flags fail with ```InvalidInput``` before the system call is made, see ```OpenFlags::validate()```
+   ///
+   /// Handles whose ```handle_bytes``` is larger than ```MAX_HANDLE_SZ``` or than the stored payload are rejected with ```InvalidInput``` before the system call is made, so that an ```EINVAL``` returned by the kernel means that the file-system did not accept the encoding (see ```is_outdated_encoding()```). A handle of a file that has been deleted fails with ```ESTALE``` (see ```is_stale()```), which maps to the stale file handle errors of NFS
+   ///
    /// # Safety
    ///
    /// Usage of this function may cause security issues for privileged containers, if they have some file-systems bind-mounted into them with limited visibility (i.e. only a subdirectory or a file is bind-mounted into the container, not the entire file-system). A privileged process can open a file that is not accessible by a path using ```open_by_handle_at()```, if it manages to acquire or guess its file handle. File servers operating in privileged containers that use this function should always check what the file descriptor they have acquired using this function refers to
-   pub unsafe fn open_by_handle(&self, mnt_fd: BorrowedFd<'_>, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   pub unsafe fn open_by_handle(&self, mnt_fd: impl AsFd, flags: OpenFlags) -> std::io::Result<OwnedFd>
    {
+      flags.validate()?;
       let f = flags.bits();
-      let mut v_dup = Vec::<u32>::new();
-      v_dup.try_reserve(self.v.len())?;
-      v_dup.extend_from_slice(&self.v);
-      let r = unsafe { open_by_handle_at(mnt_fd.try_clone_to_owned()?.as_raw_fd(), v_dup.as_mut_ptr() as *mut file_handle, Self::get_signed(f)?) };
+      if !self.is_structurally_valid() { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "structurally invalid file handle")); }
+      // open_by_handle_at() does not modify the handle
+      let r = unsafe { open_by_handle_at(mnt_fd.as_fd().as_raw_fd(), self.v.as_ptr() as *const file_handle as *mut file_handle, Self::get_signed(f)?) };
       if r >= 0
       {
          unsafe { Ok(OwnedFd::from_raw_fd(r)) }
       }
       else
       {
-         Err(std::io::Error::last_os_error())
+         Err(Self::open_error())
+      }
+   }
+   
+   /// Returns the error of a failed ```open_by_handle_at()```, turning ```EPERM``` into ```HandleError::MissingCapability``` if ```CAP_DAC_READ_SEARCH``` is not effective
+   fn open_error() -> std::io::Error
+   {
+      let err = std::io::Error::last_os_error();
+      if err.raw_os_error().and_then(|e| u32::try_from(e).ok()) == Some(EPERM) && !has_open_capability() { return HandleError::MissingCapability(err).into(); }
+      err
+   }
+   
+   /// Same as ```open_by_handle()```, but gives the file created with ```O_TMPFILE``` the permission bits ```mode``` (e.g. ```0o600```), like the ```mode``` argument of ```openat()```
+   ///
+   /// The kernel creates the file with no permission bits at all, so they are set with ```fchmod()``` right after it is opened, and the umask is not applied. With ```O_CREAT```, ```mode``` is ignored: a handle always refers to an existing file, so nothing is created, just like ```open()``` ignores ```mode``` when the file already exists. Without either flag, ```mode``` is ignored as well
+   ///
+   /// # Safety
+   ///
+   /// See ```open_by_handle()```
+   pub unsafe fn open_by_handle_with_mode(&self, mnt_fd: impl AsFd, flags: OpenFlags, mode: u32) -> std::io::Result<OwnedFd>
+   {
+      let tmpfile = flags.contains(OpenFlags::O_TMPFILE);
+      let fd = unsafe { self.open_by_handle(mnt_fd, flags)? };
+      if tmpfile && unsafe { fchmod(fd.as_raw_fd(), mode & 0o7777) } != 0 { return Err(std::io::Error::last_os_error()); }
+      Ok(fd)
+   }
+   
+   /// Same as ```open_by_handle()```, but fails with ```PermissionDenied``` (closing the descriptor) unless the opened file is on one of the devices in ```allowed_devs``` (```st_dev``` values, e.g. from ```MetadataExt::dev()``` of the exported directories), so that a file server can refuse handles that resolve outside its file-systems
+   ///
+   /// # Safety
+   ///
+   /// See ```open_by_handle()```: the check covers handles of other file-systems, but a handle of a file on an allowed file-system that is outside a bind-mounted subdirectory still opens
+   pub unsafe fn open_by_handle_checked(&self, mnt_fd: impl AsFd, flags: OpenFlags, allowed_devs: &[u64]) -> std::io::Result<OwnedFd>
+   {
+      let fd = unsafe { self.open_by_handle(mnt_fd, flags)? };
+      let dev = Self::fstat_fd(fd.as_fd())?.st_dev;
+      if !allowed_devs.contains(&dev) { return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "file handle resolves to a file on a file-system that is not allowed")); }
+      Ok(fd)
+   }
+   
+   /// Returns a path of the file the handle refers to, for debugging and logs: the handle is opened with ```O_PATH``` and the ```/proc/self/fd``` link of the descriptor is read (the descriptor is closed right away)
+   ///
+   /// This requires the same privileges as ```open_by_handle()```. The path is only a snapshot: the file may have been renamed or removed since (the link then ends with ``` (deleted)```), a file with several hard links gives one of them, and the path of a file that the kernel could not reconnect to its directory (which can happen for files that are not directories) or that is outside the root directory of the process may not lead to the file
+   ///
+   /// # Safety
+   ///
+   /// See ```open_by_handle()```: the path of a file outside a bind-mounted subdirectory is revealed just like the file would be opened
+   pub unsafe fn resolve_path(&self, mnt_fd: impl AsFd) -> std::io::Result<PathBuf>
+   {
+      let fd = unsafe { self.open_by_handle(mnt_fd, OpenFlags::O_PATH | OpenFlags::O_CLOEXEC)? };
+      std::fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+   }
+   
+   /// Checks that the header is present and that ```handle_bytes``` fits both ```MAX_HANDLE_SZ``` and the stored payload
+   fn is_structurally_valid(&self) -> bool
+   {
+      Self::words_are_valid(&self.v)
+   }
+   
+   fn words_are_valid(v: &[u32]) -> bool
+   {
+      match v.first()
+      {
+         Some(handle_bytes) => v.len() >= 2 && *handle_bytes <= MAX_HANDLE_SZ && (*handle_bytes as usize) <= (v.len() - 2) * 4,
+         None => false,
       }
    }
    
-   /// Similar to ```clone()```, but uses fallible memory allocation API
+   /// Checks whether the file the handle refers to still has the generation ```expected_gen``` (e.g. one decoded by ```debug_dump()``` when the handle was stored), so that backup tools can detect generation bumps around a snapshot
+   ///
+   /// Some file-systems can change the generation numbers of inodes when they are frozen and thawed, or restored from a snapshot, and a handle always embeds the generation it was obtained with: the generic decoders check it against the inode, so once the generation changes (including when the inode number has been reused by another file), opening the handle fails with ```ESTALE```. The generation embedded in the handle is decoded and compared with ```expected_gen```, then the handle is opened with ```O_PATH``` (and the descriptor closed right away) to check that it is still current. Returns ```Ok(false)``` if the generations differ or the handle is stale, and fails with ```Unsupported``` if the file-system does not use one of the generic handle formats (```FILEID_INO32_GEN```, ```FILEID_INO64_GEN``` and their ```_PARENT``` variants), whose generation cannot be decoded
+   ///
+   /// # Safety
+   ///
+   /// See ```open_by_handle()```
+   pub unsafe fn generation_stable(&self, mnt_fd: impl AsFd, expected_gen: u32) -> std::io::Result<bool>
+   {
+      let generation = match fileid::decode_ino_gen(self.get_handle_type(), self.payload_bytes())
+      {
+         Some((_, generation)) => generation,
+         None => return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "generation cannot be decoded for this handle type")),
+      };
+      if generation != expected_gen { return Ok(false); }
+      match unsafe { self.open_by_handle(mnt_fd, OpenFlags::O_PATH | OpenFlags::O_CLOEXEC) }
+      {
+         Ok(_) => Ok(true),
+         Err(e) if e.raw_os_error() == Some(ESTALE as i32) => Ok(false),
+         Err(e) => Err(e),
+      }
+   }
+   
+   /// Same as ```clone()```, but returns an error instead of panicking if the memory cannot be allocated
    pub fn duplicate(&self) -> Result<LinuxFileHandle,std::collections::TryReserveError>
    {
       let mut v_dup = Vec::<u32>::new();
       v_dup.try_reserve(self.v.len())?;
       v_dup.extend_from_slice(&self.v);
-      Ok(LinuxFileHandle { v: v_dup, mnt_id: self.mnt_id })
+      Ok(LinuxFileHandle { v: v_dup, mnt_id: self.mnt_id, at_flags: self.at_flags, unique_mnt_id: self.unique_mnt_id })
+   }
+}
+
+impl Clone for LinuxFileHandle
+{
+   /// Copies the handle with ```duplicate()```
+   ///
+   /// # Panics
+   ///
+   /// Panics if the memory cannot be allocated
+   fn clone(&self) -> LinuxFileHandle
+   {
+      self.duplicate().expect("memory allocation failed while cloning a file handle")
    }
 }
 
@@ -236,6 +1365,195 @@ impl TryFrom<&[u32]> for LinuxFileHandle
       let mut v_dup = Vec::<u32>::new();
       v_dup.try_reserve(value.len())?;
       v_dup.extend_from_slice(value);
-      Ok(LinuxFileHandle { v: v_dup, mnt_id: -1 })
+      Ok(LinuxFileHandle { v: v_dup, mnt_id: -1, at_flags: None, unique_mnt_id: None })
+   }
+}
+
+impl TryFrom<&[u8]> for LinuxFileHandle
+{
+   type Error = HandleError;
+   
+   /// Same as ```from_vec()```
+   fn try_from(value: &[u8]) -> Result<LinuxFileHandle,HandleError>
+   {
+      LinuxFileHandle::from_vec_impl(value)
+   }
+}
+
+impl TryFrom<Vec<u8>> for LinuxFileHandle
+{
+   type Error = HandleError;
+   
+   /// Same as ```from_vec()```, consuming the buffer
+   ///
+   /// The allocation of the buffer cannot be reused for the words of the handle, since memory has to be freed with the alignment it was allocated with (1 for ```u8```, 4 for ```u32```), so the bytes are still copied; the buffer is freed right away
+   fn try_from(value: Vec<u8>) -> Result<LinuxFileHandle,HandleError>
+   {
+      LinuxFileHandle::from_vec_impl(&value)
+   }
+}
+
+impl TryFrom<LinuxFileHandle> for Vec<u8>
+{
+   type Error = TryReserveError;
+   
+   /// Same as ```get_vec()```
+   fn try_from(value: LinuxFileHandle) -> Result<Vec<u8>,TryReserveError>
+   {
+      value.get_vec()
+   }
+}
+
+/// Same as ```as_bytes()```, e.g. for ```writer.write_all(handle.as_ref())```
+impl AsRef<[u8]> for LinuxFileHandle
+{
+   fn as_ref(&self) -> &[u8]
+   {
+      self.as_bytes()
+   }
+}
+
+/// Handles are equal if their ```handle_bytes```, ```handle_type``` and the first ```handle_bytes``` bytes of their payloads are equal
+///
+/// The mount ID is deliberately not compared: two handles of the same i-node obtained by different calls, possibly through different mounts of the same file-system, are equal, which is what deduplication and lookups by handle need. This also means that handles of different file-systems can be equal, so a key that must be unique across file-systems should combine the handle with the file-system (e.g. its UUID, see ```fs_uuid()```). Padding words past ```handle_bytes``` are not compared either
+impl PartialEq for LinuxFileHandle
+{
+   fn eq(&self, other: &LinuxFileHandle) -> bool
+   {
+      self.v.iter().take(2).eq(other.v.iter().take(2)) && self.payload_iter().eq(other.payload_iter())
+   }
+}
+
+impl Eq for LinuxFileHandle {}
+
+/// Consistent with ```PartialEq```: the mount ID and the padding are not hashed
+impl std::hash::Hash for LinuxFileHandle
+{
+   fn hash<H: std::hash::Hasher>(&self, state: &mut H)
+   {
+      for w in self.v.iter().take(2)
+      {
+         w.hash(state);
+      }
+      for b in self.payload_iter()
+      {
+         b.hash(state);
+      }
+   }
+}
+
+/// Prints the handle on a single line, as the mount ID, ```handle_bytes``` and the header and payload in hex (without padding), e.g. ```LinuxFileHandle { mnt_id: 28, handle_bytes: 8, bytes: 0800000001000000020000002a1b3c4d }```
+impl std::fmt::Debug for LinuxFileHandle
+{
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+   {
+      let bytes = self.to_hex();
+      match self.get_mnt_id()
+      {
+         Some(id) => write!(f, "LinuxFileHandle {{ mnt_id: {}, handle_bytes: {}, bytes: {} }}", id, self.v.first().copied().unwrap_or(0), bytes),
+         None => write!(f, "LinuxFileHandle {{ mnt_id: none, handle_bytes: {}, bytes: {} }}", self.v.first().copied().unwrap_or(0), bytes),
+      }
+   }
+}
+
+/// Prints ```to_hex()```
+impl std::fmt::Display for LinuxFileHandle
+{
+   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+   {
+      f.write_str(&self.to_hex())
+   }
+}
+
+#[cfg(test)]
+mod tests
+{
+   use super::*;
+
+   #[test]
+   fn open_errors_are_classified()
+   {
+      use crate::probe::classify_open_error;
+      assert_eq!(classify_open_error(Some(EPERM), true, false), OpenAvailability::BlockedBySeccomp);
+      // the capability is effective, but only in a user namespace
+      assert_eq!(classify_open_error(Some(EPERM), true, true), OpenAvailability::MissingCapability);
+      assert_eq!(classify_open_error(Some(EPERM), false, false), OpenAvailability::MissingCapability);
+      assert_eq!(classify_open_error(Some(EACCES), false, true), OpenAvailability::BlockedBySeccomp);
+      assert_eq!(classify_open_error(Some(ENOSYS), true, false), OpenAvailability::NotSupported);
+      assert_eq!(classify_open_error(None, true, false), OpenAvailability::NotSupported);
+   }
+
+   #[test]
+   fn unique_mnt_id_falls_back_on_einval()
+   {
+      // AT_SYMLINK_NOFOLLOW is rejected with EINVAL, like AT_HANDLE_MNT_ID_UNIQUE on kernels older than 6.12
+      let fh = LinuxFileHandle::obtain_unique_impl(None, b"/", AT_HANDLE_MNT_ID_UNIQUE | AT_SYMLINK_NOFOLLOW).unwrap();
+      assert_eq!(fh.get_unique_mnt_id(), None);
+      assert!(fh.get_mnt_id().is_some());
+      assert_eq!(fh.get_at_flags(), Some(AtFlags::empty()));
+      assert_eq!(fh, LinuxFileHandle::obtain(None, "/").unwrap());
+   }
+
+   /// A stand-in for ```name_to_handle_at()``` that reports the handle sizes in ```sizes``` with ```EOVERFLOW``` one after the other, then succeeds
+   fn growing_stub<'c>(sizes: &'c [u32], calls: &'c mut usize) -> impl FnMut(&mut [u32]) -> std::io::Result<()> + 'c
+   {
+      let final_size = sizes.last().copied().unwrap_or(0);
+      let mut sizes = sizes.iter().copied();
+      move |buf| {
+         *calls += 1;
+         // the buffer must always be as large as handle_bytes says
+         assert!(buf.len() >= 2 + (buf[0] as usize).div_ceil(4));
+         match sizes.next()
+         {
+            Some(size) if size > buf[0] => { buf[0] = size; Err(std::io::Error::from_raw_os_error(EOVERFLOW as i32)) },
+            _ => { buf[0] = final_size; buf[1] = 1; Ok(()) },
+         }
+      }
+   }
+
+   #[cfg(not(feature = "no-size-probe"))]
+   #[test]
+   fn call_sized_follows_growing_size()
+   {
+      let mut calls = 0;
+      let fh = LinuxFileHandle::call_sized(0, growing_stub(&[8, 16], &mut calls)).unwrap();
+      assert_eq!(calls, 3);
+      assert_eq!(fh.len(), 2 + 4);
+      assert_eq!(fh[0], 16);
+   }
+
+   #[cfg(not(feature = "no-size-probe"))]
+   #[test]
+   fn call_sized_gives_up()
+   {
+      let mut calls = 0;
+      let sizes: Vec<u32> = (1..=MAX_SIZING_ATTEMPTS as u32).map(|i| i * 8).collect();
+      let err = LinuxFileHandle::call_sized(0, growing_stub(&sizes, &mut calls)).unwrap_err();
+      assert_eq!(calls, MAX_SIZING_ATTEMPTS);
+      assert_eq!(err.kind(), std::io::ErrorKind::Other);
+   }
+
+   #[cfg(feature = "no-size-probe")]
+   #[test]
+   fn call_sized_skips_probe()
+   {
+      let mut calls = 0;
+      let fh = LinuxFileHandle::call_sized(0, growing_stub(&[8], &mut calls)).unwrap();
+      assert_eq!(calls, 1);
+      assert_eq!(fh.len(), 2 + 2);
+      // a kernel asking for more than MAX_HANDLE_SZ still gets it
+      let mut calls = 0;
+      let fh = LinuxFileHandle::call_sized(0, growing_stub(&[MAX_HANDLE_SZ + 8], &mut calls)).unwrap();
+      assert_eq!(calls, 2);
+      assert_eq!(fh[0], MAX_HANDLE_SZ + 8);
+   }
+
+   #[test]
+   fn call_sized_returns_other_errors()
+   {
+      let mut calls = 0;
+      let err = LinuxFileHandle::call_sized(0, |_| { calls += 1; Err(std::io::Error::from_raw_os_error(ENOENT as i32)) }).unwrap_err();
+      assert_eq!(calls, 1);
+      assert_eq!(err.raw_os_error(), Some(ENOENT as i32));
    }
 }
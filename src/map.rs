@@ -0,0 +1,92 @@
+//! Handles keyed by application-level identifiers
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::os::fd::AsFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+
+/// Stores handles under keys chosen by the application, for the common pattern of resolving a logical name to a handle once and reopening it many times
+///
+/// Every handle keeps the mount ID it was obtained with, see ```LinuxFileHandle::get_mnt_id()```
+pub struct HandleMap<K>
+{
+   handles: HashMap<K, LinuxFileHandle>,
+}
+
+impl<K: Eq + Hash> HandleMap<K>
+{
+   /// Creates an empty map
+   pub fn new() -> HandleMap<K>
+   {
+      HandleMap { handles: HashMap::new() }
+   }
+
+   /// Obtains the handle of ```path``` (see ```LinuxFileHandle::obtain()```) and stores it under ```key```, returning the handle previously stored under it
+   pub fn insert_obtained(&mut self, key: K, dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<Option<LinuxFileHandle>>
+   {
+      let fh = LinuxFileHandle::obtain(dirfd, path)?;
+      Ok(self.insert(key, fh)?)
+   }
+
+   /// Stores an already obtained handle under ```key```, returning the handle previously stored under it
+   pub fn insert(&mut self, key: K, fh: LinuxFileHandle) -> Result<Option<LinuxFileHandle>,std::collections::TryReserveError>
+   {
+      self.handles.try_reserve(1)?;
+      Ok(self.handles.insert(key, fh))
+   }
+
+   /// Returns the handle stored under ```key```
+   pub fn get(&self, key: &K) -> Option<&LinuxFileHandle>
+   {
+      self.handles.get(key)
+   }
+
+   /// Removes the handle stored under ```key``` and returns it
+   pub fn remove(&mut self, key: &K) -> Option<LinuxFileHandle>
+   {
+      self.handles.remove(key)
+   }
+
+   /// Opens the handle stored under ```key```, failing with ```NotFound``` if there is none (see ```LinuxFileHandle::open_by_handle()```)
+   ///
+   /// # Safety
+   ///
+   /// See ```LinuxFileHandle::open_by_handle()```
+   pub unsafe fn open(&self, key: &K, mnt_fd: impl AsFd, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   {
+      match self.handles.get(key)
+      {
+         Some(fh) => unsafe { fh.open_by_handle(mnt_fd, flags) },
+         None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no file handle stored under the key")),
+      }
+   }
+
+   /// Iterates over all keys and handles, in no particular order
+   pub fn iter(&self) -> impl Iterator<Item = (&K, &LinuxFileHandle)>
+   {
+      self.handles.iter()
+   }
+
+   /// Number of stored handles
+   pub fn len(&self) -> usize
+   {
+      self.handles.len()
+   }
+
+   /// Checks whether the map is empty
+   pub fn is_empty(&self) -> bool
+   {
+      self.handles.is_empty()
+   }
+}
+
+impl<K: Eq + Hash> Default for HandleMap<K>
+{
+   fn default() -> HandleMap<K>
+   {
+      HandleMap::new()
+   }
+}
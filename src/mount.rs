@@ -0,0 +1,468 @@
+//! Lookup of mounts by the mount ID returned by ```name_to_handle_at()```
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::BufRead;
+use std::os::unix::ffi::OsStringExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::fs::MetadataExt;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+use crate::ffi_bindings::*;
+
+/// An entry of ```/proc/self/mountinfo```, see ```proc_pid_mountinfo(5)```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry
+{
+   /// Mount ID, the same value ```name_to_handle_at()``` returns
+   pub mount_id: i32,
+   /// Mount ID of the parent mount
+   pub parent_id: i32,
+   /// Major and minor numbers of the device of the file-system
+   pub dev: (u32, u32),
+   /// Directory of the file-system that forms the root of this mount
+   pub root: PathBuf,
+   /// Mount point, relative to the root directory of the process
+   pub mount_point: PathBuf,
+   /// File-system type
+   pub fs_type: String,
+   /// Mount source, e.g. the block device
+   pub source: String,
+}
+
+/// An indexed snapshot of ```/proc/self/mountinfo```
+///
+/// Container hosts can have thousands of mounts, so parsing the whole file for every handle to resolve is expensive. The table is parsed once and then looks mounts up by mount ID in constant time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountTable
+{
+   entries: HashMap<i32, MountEntry>,
+   max_entries: usize,
+}
+
+/// Waits for changes of the mount table
+///
+/// ```/proc/self/mountinfo``` reports ```POLLPRI``` and ```POLLERR``` in ```poll()``` whenever a mount is added to or removed from the mount namespace since the previous ```poll()``` (or since the file was opened). The watcher keeps the file open and polls it, which lets a long-running server refresh its ```MountTable``` only when something has changed
+pub struct MountWatcher
+{
+   file: std::fs::File,
+}
+
+/// Number of the ```statmount()``` system call (Linux 6.8 or later), which is not in the headers yet; it is the same in the numbering shared by all architectures since Linux 5.1
+const SYS_STATMOUNT: i64 = 457;
+/// Asks ```statmount()``` for the basic information of the mount, including ```mnt_id_old```
+const STATMOUNT_MNT_BASIC: u64 = 0x2;
+/// Offset of ```mnt_id_old``` in ```struct statmount```
+const STATMOUNT_MNT_ID_OLD_OFFSET: usize = 56;
+
+/// ```struct mnt_id_req``` of ```statmount()```, in its first version
+#[repr(C)]
+struct MntIdReq
+{
+   size: u32,
+   spare: u32,
+   mnt_id: u64,
+   param: u64,
+}
+
+/// Returns the 32-bit mount ID (the one in ```/proc/self/mountinfo```) of the mount with the 64-bit unique mount ID ```unique_id```, or None if there is no such mount in the mount namespace (or ```statmount()``` fails)
+fn old_mnt_id(unique_id: u64) -> Option<i32>
+{
+   let req = MntIdReq { size: std::mem::size_of::<MntIdReq>() as u32, spare: 0, mnt_id: unique_id, param: STATMOUNT_MNT_BASIC };
+   // large enough for struct statmount, no strings are requested
+   let mut buf: [u64; 128] = [0; 128];
+   let r = unsafe { syscall(SYS_STATMOUNT, &req as *const MntIdReq, buf.as_mut_ptr(), std::mem::size_of_val(&buf), 0u32) };
+   if r != 0 { return None; }
+   let bytes = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, std::mem::size_of_val(&buf)) };
+   let old: [u8; 4] = bytes[STATMOUNT_MNT_ID_OLD_OFFSET..STATMOUNT_MNT_ID_OLD_OFFSET + 4].try_into().ok()?;
+   i32::try_from(u32::from_ne_bytes(old)).ok()
+}
+
+fn malformed() -> std::io::Error
+{
+   std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed mountinfo entry")
+}
+
+/// Decodes the octal escapes (```\040``` and the like) used for spaces, tabs, newlines and backslashes in mountinfo
+fn unescape(field: &[u8]) -> Vec<u8>
+{
+   let mut result = Vec::<u8>::with_capacity(field.len());
+   let mut i: usize = 0;
+   while i < field.len()
+   {
+      if field[i] == b'\\' && field.len() >= i + 4 && field[i + 1..i + 4].iter().all(|c| (b'0'..=b'7').contains(c))
+      {
+         let code = field[i + 1..i + 4].iter().fold(0u32, |acc, c| acc * 8 + u32::from(c - b'0'));
+         result.push(code as u8);
+         i += 4;
+      }
+      else
+      {
+         result.push(field[i]);
+         i += 1;
+      }
+   }
+   result
+}
+
+fn parse_number<T: std::str::FromStr>(field: &[u8]) -> std::io::Result<T>
+{
+   match std::str::from_utf8(field).ok().and_then(|f| f.parse().ok())
+   {
+      Some(n) => Ok(n),
+      None => Err(malformed()),
+   }
+}
+
+/// Splits a ```dev_t``` into the major and minor numbers, see ```makedev(3)```
+fn split_dev(dev: u64) -> (u32, u32)
+{
+   let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+   let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+   (major as u32, minor as u32)
+}
+
+/// Parses the name of a link in ```/dev/disk/by-uuid```, which is a 128-bit UUID only for some file-systems (e.g. FAT volume IDs are 32-bit)
+fn parse_uuid(name: &str) -> Option<[u8; 16]>
+{
+   let digits: String = name.chars().filter(|c| *c != '-').collect();
+   crate::hex::decode(&digits)?.try_into().ok()
+}
+
+impl MountEntry
+{
+   /// Parses a line of ```/proc/self/mountinfo``` (without the trailing newline)
+   fn parse(line: &[u8]) -> std::io::Result<MountEntry>
+   {
+      let mut fields = line.split(|c| *c == b' ');
+      let mut next = || fields.next().ok_or_else(malformed);
+      let mount_id = parse_number(next()?)?;
+      let parent_id = parse_number(next()?)?;
+      let dev_field = next()?;
+      let colon = dev_field.iter().position(|c| *c == b':').ok_or_else(malformed)?;
+      let dev = (parse_number(&dev_field[..colon])?, parse_number(&dev_field[colon + 1..])?);
+      let root = PathBuf::from(OsString::from_vec(unescape(next()?)));
+      let mount_point = PathBuf::from(OsString::from_vec(unescape(next()?)));
+      let _options = next()?;
+      // optional fields, terminated by a single hyphen
+      while next()? != b"-" {}
+      let fs_type = String::from_utf8_lossy(&unescape(next()?)).into_owned();
+      let source = String::from_utf8_lossy(&unescape(next()?)).into_owned();
+      Ok(MountEntry { mount_id, parent_id, dev, root, mount_point, fs_type, source })
+   }
+}
+
+impl MountTable
+{
+   /// Parses ```/proc/self/mountinfo```
+   pub fn load() -> std::io::Result<MountTable>
+   {
+      Self::load_with_limit(usize::MAX)
+   }
+
+   /// Same as ```load()```, but fails with ```InvalidData``` rather than parse more than ```max_entries``` entries, which bounds the worst-case latency on hosts with huge mount tables
+   pub fn load_with_limit(max_entries: usize) -> std::io::Result<MountTable>
+   {
+      let mut reader = std::io::BufReader::new(std::fs::File::open("/proc/self/mountinfo")?);
+      let mut entries = HashMap::<i32, MountEntry>::new();
+      let mut line = Vec::<u8>::new();
+      loop
+      {
+         line.clear();
+         if reader.read_until(b'\n', &mut line)? == 0 { break; }
+         if line.last() == Some(&b'\n') { line.pop(); }
+         if line.is_empty() { continue; }
+         if entries.len() >= max_entries { return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "too many mountinfo entries")); }
+         let entry = MountEntry::parse(&line)?;
+         entries.try_reserve(1)?;
+         entries.insert(entry.mount_id, entry);
+      }
+      Ok(MountTable { entries, max_entries })
+   }
+
+   /// Parses ```/proc/self/mountinfo``` again (with the same entry limit the table was loaded with) and replaces the contents of the table, returning whether anything has changed
+   pub fn refresh(&mut self) -> std::io::Result<bool>
+   {
+      let fresh = Self::load_with_limit(self.max_entries)?;
+      if fresh.entries == self.entries { return Ok(false); }
+      self.entries = fresh.entries;
+      Ok(true)
+   }
+
+   /// Looks up the mount with the given mount ID
+   pub fn lookup(&self, mnt_id: i32) -> Option<&MountEntry>
+   {
+      self.entries.get(&mnt_id)
+   }
+
+   /// Returns the mount point of the mount with the given mount ID
+   pub fn mount_point(&self, mnt_id: i32) -> Option<&Path>
+   {
+      self.lookup(mnt_id).map(|e| e.mount_point.as_path())
+   }
+
+   /// Returns the UUID of the file-system of the mount, if it has one
+   ///
+   /// The UUID is looked up the way ```blkid``` exposes it, in the ```/dev/disk/by-uuid``` links maintained by udev: a link matches if it points to the device of the mount, or to its source (file-systems like btrfs report an anonymous device number in mountinfo). Returns None for file-systems without a 128-bit UUID (tmpfs, procfs, FAT and the like), and if there is no ```/dev/disk/by-uuid``` (e.g. in containers)
+   pub fn fs_uuid(&self, mnt_id: i32) -> std::io::Result<Option<[u8; 16]>>
+   {
+      let entry = match self.lookup(mnt_id)
+      {
+         Some(e) => e,
+         None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "mount is not in the table")),
+      };
+      let links = match std::fs::read_dir("/dev/disk/by-uuid")
+      {
+         Ok(l) => l,
+         Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+         Err(e) => return Err(e),
+      };
+      let source = std::fs::canonicalize(&entry.source).ok();
+      for link in links
+      {
+         let link = link?;
+         let target = match std::fs::canonicalize(link.path())
+         {
+            Ok(t) => t,
+            Err(_) => continue, // dangling link
+         };
+         let matches = Some(&target) == source.as_ref() || std::fs::metadata(&target).map(|m| split_dev(m.rdev()) == entry.dev).unwrap_or(false);
+         if !matches { continue; }
+         if let Some(uuid) = link.file_name().to_str().and_then(parse_uuid) { return Ok(Some(uuid)); }
+      }
+      Ok(None)
+   }
+
+   /// Iterates over all entries of the table, in no particular order
+   pub fn iter(&self) -> impl Iterator<Item = &MountEntry>
+   {
+      self.entries.values()
+   }
+
+   /// Number of mounts in the table
+   pub fn len(&self) -> usize
+   {
+      self.entries.len()
+   }
+
+   /// Checks whether the table is empty
+   pub fn is_empty(&self) -> bool
+   {
+      self.entries.is_empty()
+   }
+}
+
+impl MountWatcher
+{
+   /// Starts watching the mount namespace of the calling process
+   pub fn new() -> std::io::Result<MountWatcher>
+   {
+      Ok(MountWatcher { file: std::fs::File::open("/proc/self/mountinfo")? })
+   }
+
+   /// Waits until the mount table changes, or until ```timeout``` expires (None waits indefinitely). Returns whether a change was reported
+   pub fn wait(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<bool>
+   {
+      let timeout_ms: i32 = match timeout
+      {
+         Some(t) => t.as_millis().try_into().unwrap_or(i32::MAX),
+         None => -1,
+      };
+      let mut pfd = pollfd { fd: self.file.as_raw_fd(), events: (POLLPRI | POLLERR) as std::os::raw::c_short, revents: 0 };
+      let r = unsafe { poll(&mut pfd as *mut pollfd, 1, timeout_ms) };
+      if r < 0 { return Err(std::io::Error::last_os_error()); }
+      Ok(r > 0 && (pfd.revents as u32) & (POLLPRI | POLLERR) != 0)
+   }
+}
+
+/// Returns the distinct mount IDs of ```handles``` in ascending order, leaving out handles without one, e.g. to decide how many mount file descriptors to keep open for a batch of handles
+pub fn distinct_mounts(handles: &[LinuxFileHandle]) -> Vec<i32>
+{
+   let mut result: Vec<i32> = handles.iter().filter_map(|h| h.get_mnt_id()).collect();
+   result.sort_unstable();
+   result.dedup();
+   result
+}
+
+/// Opens the mount point of the mount with ID ```mnt_id``` (as found in ```/proc/self/mountinfo```), to be used as ```mnt_fd``` of ```open_by_handle()```
+///
+/// The mount point is opened with ```O_RDONLY | O_DIRECTORY``` rather than ```O_PATH```, since ```open_by_handle_at()``` does not accept ```O_PATH``` descriptors; mounts of a single file fail with ```ENOTDIR```. If another mount now hides the mount point, the device of the opened directory does not match and ```NotFound``` is returned, just like for a mount ID that is not in the table. The mount table is loaded for every call, see ```MountTable```
+pub fn mount_fd_for_id(mnt_id: i32) -> std::io::Result<OwnedFd>
+{
+   let table = MountTable::load()?;
+   let entry = match table.lookup(mnt_id)
+   {
+      Some(e) => e,
+      None => return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "mount ID is not in the mount table")),
+   };
+   let dir = std::fs::File::options().read(true).custom_flags(LinuxFileHandle::get_signed(O_DIRECTORY)?).open(&entry.mount_point)?;
+   if split_dev(dir.metadata()?.dev()) != entry.dev { return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "mount point is hidden by another mount")); }
+   Ok(dir.into())
+}
+
+/// Keeps the mount file descriptors opened by ```mount_fd_for_id()```, keyed by mount ID, so that opening many handles of the same mount loads the mount table and opens the mount point only once, see ```LinuxFileHandle::open_with_cache()```
+///
+/// An open descriptor keeps its mount busy: the file-system can only be detached lazily while the cache holds it, and stays alive until the descriptor is closed with ```invalidate()``` or ```clear()``` (or when the cache is dropped)
+pub struct MountFdCache
+{
+   fds: HashMap<i32, OwnedFd>,
+}
+
+impl MountFdCache
+{
+   /// Creates an empty cache
+   pub fn new() -> MountFdCache
+   {
+      MountFdCache { fds: HashMap::new() }
+   }
+
+   /// Returns the descriptor for the mount ```mnt_id```, opening it with ```mount_fd_for_id()``` if it is not in the cache yet
+   pub fn get(&mut self, mnt_id: i32) -> std::io::Result<BorrowedFd<'_>>
+   {
+      if !self.fds.contains_key(&mnt_id)
+      {
+         let fd = mount_fd_for_id(mnt_id)?;
+         self.fds.try_reserve(1)?;
+         self.fds.insert(mnt_id, fd);
+      }
+      Ok(self.fds[&mnt_id].as_fd())
+   }
+
+   /// Closes the descriptor for the mount ```mnt_id```, if there is one, so that the next ```get()``` opens the mount point again
+   pub fn invalidate(&mut self, mnt_id: i32)
+   {
+      self.fds.remove(&mnt_id);
+   }
+
+   /// Closes all descriptors
+   pub fn clear(&mut self)
+   {
+      self.fds.clear();
+   }
+
+   /// Number of open descriptors
+   pub fn len(&self) -> usize
+   {
+      self.fds.len()
+   }
+
+   /// Checks whether the cache holds no descriptor
+   pub fn is_empty(&self) -> bool
+   {
+      self.fds.is_empty()
+   }
+}
+
+impl Default for MountFdCache
+{
+   fn default() -> MountFdCache
+   {
+      MountFdCache::new()
+   }
+}
+
+impl LinuxFileHandle
+{
+   /// Same as ```obtain()```, but also returns a file descriptor on the mount of the file (see ```open_mount_fd()```), so that the handle can be passed to ```open_by_handle()``` right away
+   ///
+   /// The descriptor is not an ```O_PATH``` one, which ```open_by_handle_at()``` would reject, but the mount point opened with ```O_RDONLY | O_DIRECTORY```. This fails if the mount is not reachable by its mount point (e.g. hidden by another mount) or is a mount of a single file
+   pub fn obtain_full(dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<(LinuxFileHandle, OwnedFd)>
+   {
+      let fh = LinuxFileHandle::obtain(dirfd, path)?;
+      let mnt_fd = fh.open_mount_fd()?;
+      Ok((fh, mnt_fd))
+   }
+
+   /// Opens the mount point of the mount the handle was obtained from with ```mount_fd_for_id()```, so that a handle can be opened without keeping track of the file it was obtained from (fails with ```InvalidInput``` for handles without a mount ID)
+   pub fn open_mount_fd(&self) -> std::io::Result<OwnedFd>
+   {
+      match self.get_mnt_id()
+      {
+         Some(mnt_id) => mount_fd_for_id(mnt_id),
+         None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "file handle has no mount ID")),
+      }
+   }
+
+   /// Same as ```open_by_handle()```, with the descriptor for the mount of the handle taken from ```cache``` (fails with ```InvalidInput``` for handles without a mount ID)
+   ///
+   /// If opening fails with ```EBADF```, the cached descriptor is no longer usable: it is closed, the mount point is opened again and the handle is opened once more, returning the result of that attempt. ```ESTALE``` is returned right away (see ```is_stale()```) and the descriptor stays cached, since for a file server it is the usual answer for a handle of a deleted file, which must not cost a parse of ```/proc/self/mountinfo```. If the file-system may have been remounted (or the mount ID reused by a different mount), check the mount with ```mnt_id_still_matches()``` and call ```MountFdCache::invalidate()``` if it fails
+   ///
+   /// # Safety
+   ///
+   /// See ```open_by_handle()```
+   pub unsafe fn open_with_cache(&self, cache: &mut MountFdCache, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   {
+      let mnt_id = match self.get_mnt_id()
+      {
+         Some(m) => m,
+         None => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "file handle has no mount ID")),
+      };
+      // OpenFlags is not Copy, and the flags may be needed for a second attempt
+      let err = match unsafe { self.open_by_handle(cache.get(mnt_id)?, OpenFlags::from_bits_retain(flags.bits())) }
+      {
+         Ok(fd) => return Ok(fd),
+         Err(e) => e,
+      };
+      match err.raw_os_error().and_then(|e| u32::try_from(e).ok())
+      {
+         Some(EBADF) => {
+            cache.invalidate(mnt_id);
+            unsafe { self.open_by_handle(cache.get(mnt_id)?, flags) }
+         },
+         _ => Err(err),
+      }
+   }
+
+   /// Returns the mount point of the mount the handle was obtained from (will return None for handles without a mount ID, or if the mount is not in ```table```)
+   ///
+   /// Mount IDs are reused after unmounting, so the table should be fresh enough to still contain the original mount
+   pub fn resolve_mount_path<'t>(&self, table: &'t MountTable) -> Option<&'t Path>
+   {
+      table.mount_point(self.get_mnt_id()?)
+   }
+
+   /// Checks whether the mount ID of the handle is still in ```table```, as a cheap validation before opening a cached handle (returns false for handles without a mount ID)
+   ///
+   /// Mount IDs are reused after unmounting, and for handles that only store that reusable ID a different mount that got the same ID is not detected. To catch that, keep the ```MountEntry``` the handle was obtained on and use ```mnt_id_still_matches()```. Handles from ```obtain_with_unique_mnt_id()``` store the unique mount ID instead, which is never reused: it is looked up with ```statmount()```, and the mount must still exist and be in ```table``` under its 32-bit mount ID
+   pub fn mnt_id_still_valid(&self, table: &MountTable) -> bool
+   {
+      self.table_entry(table).is_some()
+   }
+
+   /// Looks the mount of the handle up in ```table```, by the unique mount ID if the handle has one
+   fn table_entry<'t>(&self, table: &'t MountTable) -> Option<&'t MountEntry>
+   {
+      match self.get_unique_mnt_id()
+      {
+         Some(unique_id) => table.lookup(old_mnt_id(unique_id)?),
+         None => table.lookup(self.get_mnt_id()?),
+      }
+   }
+
+   /// Same as ```mnt_id_still_valid()```, but also checks that the mount with that ID still has the device, root, file-system type and source of ```expected``` (an entry looked up when the handle was obtained), i.e. that it was not replaced by a different mount
+   pub fn mnt_id_still_matches(&self, table: &MountTable, expected: &MountEntry) -> bool
+   {
+      match self.table_entry(table)
+      {
+         Some(e) => e.mount_id == expected.mount_id && e.dev == expected.dev && e.root == expected.root && e.fs_type == expected.fs_type && e.source == expected.source,
+         None => false,
+      }
+   }
+
+   /// Returns the UUID of the file-system the handle was obtained from, if it has one (see ```MountTable::fs_uuid()```)
+   ///
+   /// Handles of different file-systems can have the same bytes, so a key that is unique across file-systems, reboots and remounts should combine the handle with the UUID rather than with the mount ID. The mount table is loaded for every call: servers resolving many handles should load a ```MountTable``` once and use ```MountTable::fs_uuid()``` directly
+   pub fn fs_uuid(&self) -> std::io::Result<Option<[u8; 16]>>
+   {
+      match self.get_mnt_id()
+      {
+         Some(mnt_id) => MountTable::load()?.fs_uuid(mnt_id),
+         None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "file handle has no mount ID")),
+      }
+   }
+}
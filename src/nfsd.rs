@@ -0,0 +1,49 @@
+//! Interoperability with the export caches of the kernel NFS server
+use crate::LinuxFileHandle;
+use crate::hex;
+
+fn malformed(msg: &'static str) -> std::io::Error
+{
+   std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// Decodes the fsid field of a cache line, either ```0x``` followed by ```%08x``` groups (one per word, in host byte order) or ```\x``` followed by the bytes in hex
+fn decode_fsid(field: &str) -> Option<Vec<u8>>
+{
+   if let Some(bytes) = field.strip_prefix("\\x") { return hex::decode(bytes); }
+   let s = field.strip_prefix("0x")?;
+   if s.is_empty() || s.len() % 8 != 0 || !s.bytes().all(|b| b.is_ascii_hexdigit()) { return None; }
+   let mut bytes = Vec::<u8>::new();
+   bytes.try_reserve(s.len() / 2).ok()?;
+   for i in (0..s.len()).step_by(8)
+   {
+      let word = u32::from_str_radix(&s[i..i + 8], 16).ok()?;
+      bytes.extend_from_slice(&word.to_ne_bytes());
+   }
+   Some(bytes)
+}
+
+impl LinuxFileHandle
+{
+   /// Parses a line of ```/proc/net/rpc/nfsd.fh/content```, the cache the kernel NFS server uses to map the file-system part of its file handles to exports
+   ///
+   /// The kernel writes the lines (see ```expkey_show()```) as ```<domain> <fsidtype> 0x<fsid> [<path>]```, the ```fsid``` being one ```%08x``` group per 32-bit word of the fsid in host byte order. The form the cache channel is written with, ```\x<fsid bytes in hex>```, is accepted as well. The returned handle has ```fsidtype``` as its ```handle_type``` and the ```fsid``` bytes as its payload. It is meant for correlating the kernel state with handles of this crate and cannot be opened. Comment lines (starting with ```#```) and blank lines are rejected with ```InvalidInput```, so that callers can skip them; malformed lines are rejected with ```InvalidData```, and an fsid larger than ```MAX_HANDLE_SZ``` with ```HandleBytesTooLarge```
+   pub fn from_nfsd_cache_line(line: &str) -> std::io::Result<LinuxFileHandle>
+   {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "not an nfsd.fh cache entry")); }
+      let mut fields = line.split_whitespace();
+      let _domain = fields.next();
+      let fsid_type: u32 = match fields.next().map(str::parse)
+      {
+         Some(Ok(t)) => t,
+         _ => return Err(malformed("invalid fsidtype in nfsd.fh cache entry")),
+      };
+      let fsid = match fields.next().and_then(decode_fsid)
+      {
+         Some(f) => f,
+         None => return Err(malformed("invalid fsid in nfsd.fh cache entry")),
+      };
+      Ok(Self::from_raw(fsid_type, &fsid)?)
+   }
+}
@@ -0,0 +1,137 @@
+//! Probes of the environment the process runs in
+use std::os::fd::AsFd;
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use crate::HandleError;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+use crate::ffi_bindings::*;
+
+/// Number of the ```CAP_DAC_READ_SEARCH``` capability, see ```capabilities(7)```
+const CAP_DAC_READ_SEARCH: u32 = 2;
+
+/// Result of ```open_by_handle_available()```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenAvailability
+{
+   /// ```open_by_handle_at()``` works
+   Available,
+   /// ```open_by_handle_at()``` was denied even though ```CAP_DAC_READ_SEARCH``` is effective, which usually means a seccomp filter (or a security module) of the container runtime blocks it
+   BlockedBySeccomp,
+   /// ```open_by_handle_at()``` was denied and ```CAP_DAC_READ_SEARCH``` is not effective, or the process is in a user namespace other than the initial one, where it is not effective for ```open_by_handle_at()``` (see ```open_requires_init_ns_cap()```)
+   MissingCapability,
+   /// The kernel does not provide ```open_by_handle_at()```, or the probe could not be carried out
+   NotSupported,
+}
+
+/// Checks whether ```CAP_DAC_READ_SEARCH```, which ```open_by_handle_at()``` requires, is in the effective capability set of the calling thread (read from ```/proc/thread-self/status```)
+///
+/// This is a cheap check that makes no system call on a handle, so it can be used to fail fast with a clear message. The capability also has to be held in the initial user namespace, see ```open_requires_init_ns_cap()```, and a seccomp filter can still block the system call, see ```open_by_handle_available()```
+pub fn has_open_capability() -> bool
+{
+   let status = match std::fs::read_to_string("/proc/thread-self/status")
+   {
+      Ok(s) => s,
+      Err(_) => return false,
+   };
+   for line in status.lines()
+   {
+      if let Some(value) = line.strip_prefix("CapEff:")
+      {
+         return match u64::from_str_radix(value.trim(), 16)
+         {
+            Ok(caps) => caps & (1 << CAP_DAC_READ_SEARCH) != 0,
+            Err(_) => false,
+         };
+      }
+   }
+   false
+}
+
+/// Checks whether ```open_by_handle_at()``` can actually be used, so that a server can fail fast with an accurate diagnosis
+///
+/// The probe obtains a file handle for the root directory and opens it with ```O_PATH```, then classifies the error (```EPERM```, ```EACCES```, ```ENOSYS```). It needs the root directory to be on a file-system that supports file handles: if it does not, ```NotSupported``` is returned
+pub fn open_by_handle_available() -> OpenAvailability
+{
+   let root = match std::fs::File::open("/")
+   {
+      Ok(f) => f,
+      Err(_) => return OpenAvailability::NotSupported,
+   };
+   let fh = match LinuxFileHandle::obtain_fd(Some(root.as_fd()))
+   {
+      Ok(h) => h,
+      Err(_) => return OpenAvailability::NotSupported,
+   };
+   // the descriptor is closed right away, so the probe does not give access to anything
+   let err = match unsafe { fh.open_by_handle(root.as_fd(), OpenFlags::O_PATH) }
+   {
+      Ok(_) => return OpenAvailability::Available,
+      Err(e) => e,
+   };
+   if let Some(HandleError::MissingCapability(_)) = err.get_ref().and_then(|e| e.downcast_ref::<HandleError>()) { return OpenAvailability::MissingCapability; }
+   classify_open_error(err.raw_os_error().and_then(|e| u32::try_from(e).ok()), has_open_capability(), open_requires_init_ns_cap())
+}
+
+/// Classifies the error of the probe of ```open_by_handle_available()```
+///
+/// ```EPERM``` with ```CAP_DAC_READ_SEARCH``` effective means a filter only if the process is in the initial user namespace: elsewhere the capability is effective in the namespace of the process, but the kernel wants it in the initial one. The kernel itself never returns ```EACCES``` for an ```O_PATH``` open by handle (there are no permission checks on the file, and the capability check fails with ```EPERM```), so ```EACCES``` can only come from a seccomp filter or a security module
+pub(crate) fn classify_open_error(errno: Option<u32>, has_capability: bool, in_user_ns: bool) -> OpenAvailability
+{
+   match errno
+   {
+      Some(EPERM) if has_capability && !in_user_ns => OpenAvailability::BlockedBySeccomp,
+      Some(EPERM) => OpenAvailability::MissingCapability,
+      Some(EACCES) => OpenAvailability::BlockedBySeccomp,
+      _ => OpenAvailability::NotSupported,
+   }
+}
+
+/// Checks whether ```mnt_fd``` is on tmpfs or ramfs
+///
+/// Such file-systems support file handles, but their inodes only live as long as the mount: after a remount (or a reboot) the same handle refers to nothing or, worse, to a different file. Handles on them should be considered valid for the lifetime of the mount only, and not be persisted
+pub fn is_ephemeral_fs(mnt_fd: BorrowedFd<'_>) -> std::io::Result<bool>
+{
+   let f_type = fs_type(mnt_fd)?;
+   Ok(f_type == TMPFS_MAGIC as __fsword_t || f_type == RAMFS_MAGIC as __fsword_t)
+}
+
+/// Checks whether the object ```fd``` refers to can have a file handle at all
+///
+/// Sockets, pipes and anonymous inodes (```eventfd()```, ```epoll_create()```, ```signalfd()``` and the like) live on internal pseudo-file-systems that do not support file handles, and ```name_to_handle_at()``` fails for them with ```EOPNOTSUPP```. This function recognizes them with ```fstat()``` and ```fstatfs()```; any other file-system without support for file handles is not detected and still makes ```obtain_fd()``` fail. Note that memfds are on the internal shmem mount, which supports file handles on recent kernels
+pub fn is_handleable_fd(fd: BorrowedFd<'_>) -> std::io::Result<bool>
+{
+   Ok(pseudo_fs_kind(fd)?.is_none())
+}
+
+/// Returns what kind of object without handles ```fd``` refers to, or None if it is not one of them
+pub(crate) fn pseudo_fs_kind(fd: BorrowedFd<'_>) -> std::io::Result<Option<&'static str>>
+{
+   if LinuxFileHandle::fstat_fd(fd)?.st_mode & S_IFMT == S_IFSOCK { return Ok(Some("socket")); }
+   let f_type = fs_type(fd)?;
+   if f_type == SOCKFS_MAGIC as __fsword_t { return Ok(Some("socket")); }
+   if f_type == PIPEFS_MAGIC as __fsword_t { return Ok(Some("pipe")); }
+   if f_type == ANON_INODE_FS_MAGIC as __fsword_t { return Ok(Some("anonymous inode")); }
+   Ok(None)
+}
+
+/// Returns the ```f_type``` magic number of the file-system of ```fd```
+fn fs_type(fd: BorrowedFd<'_>) -> std::io::Result<__fsword_t>
+{
+   let mut st = std::mem::MaybeUninit::<statfs>::uninit();
+   if unsafe { fstatfs(fd.as_raw_fd(), st.as_mut_ptr()) } != 0 { return Err(std::io::Error::last_os_error()); }
+   Ok(unsafe { st.assume_init() }.f_type)
+}
+
+/// Checks whether the calling process runs in a user namespace other than the initial one (e.g. in a rootless container), where its capabilities are not effective against the file-systems of the host
+///
+/// ```name_to_handle_at()``` does not depend on user and group IDs and works anywhere, but ```open_by_handle_at()``` requires ```CAP_DAC_READ_SEARCH``` in the initial user namespace (newer kernels relax this in some cases, e.g. for file-systems mounted inside the user namespace). If this function returns ```true```, "root" in the container usually cannot open file handles, and ```EPERM``` from ```open_by_handle()``` is expected (as ```HandleError::MissingCapability``` if the capability is not effective at all)
+pub fn open_requires_init_ns_cap() -> bool
+{
+   // the initial user namespace maps all IDs to themselves
+   match std::fs::read_to_string("/proc/self/uid_map")
+   {
+      Ok(map) => map.split_whitespace().collect::<Vec<&str>>() != ["0", "0", "4294967295"],
+      Err(_) => false, // no user namespace support
+   }
+}
@@ -0,0 +1,65 @@
+//! Bulk resolution of paths to handles with reused buffers
+use std::os::fd::AsRawFd;
+use std::os::fd::BorrowedFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use crate::AtFlags;
+use crate::LinuxFileHandle;
+use crate::MAX_HANDLE_WORDS;
+use crate::ffi_bindings::*;
+
+/// Resolves many paths to handles, reusing one buffer for the handle and one for the path
+///
+/// The handle buffer is large enough for any handle, so every lookup is a single call to ```name_to_handle_at()``` (```obtain()``` usually makes two, the first one to learn the size), and the path buffer only grows when a longer path comes along. The only allocation per lookup is the one of the returned handle, which holds just the words it uses
+pub struct HandleResolver
+{
+   buf: [u32; MAX_HANDLE_WORDS],
+   path: Vec<u8>,
+}
+
+impl HandleResolver
+{
+   /// Creates a resolver (the path buffer is allocated by the first lookup)
+   pub fn new() -> HandleResolver
+   {
+      HandleResolver { buf: [0; MAX_HANDLE_WORDS], path: Vec::new() }
+   }
+
+   /// Same as ```LinuxFileHandle::obtain()```
+   pub fn resolve(&mut self, dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<LinuxFileHandle>
+   {
+      let path = path.as_ref().as_os_str().as_bytes();
+      LinuxFileHandle::check_no_nul(path)?;
+      self.path.clear();
+      self.path.try_reserve(path.len() + 1)?;
+      self.path.extend_from_slice(path);
+      self.path.push(0);
+      let d_fd = match dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      // the words after the payload of a previous, longer handle would otherwise end up in the padding of the last word
+      self.buf.fill(0);
+      self.buf[0] = MAX_HANDLE_SZ;
+      let mut mnt_id: i32 = 0;
+      let r = unsafe { name_to_handle_at(d_fd, self.path.as_ptr() as *const i8, self.buf.as_mut_ptr() as *mut file_handle, &mut mnt_id as *mut i32, 0) };
+      if r != 0 { return Err(std::io::Error::last_os_error()); }
+      let used = 2 + LinuxFileHandle::get_usize(self.buf[0])?.div_ceil(4);
+      let mut v = Vec::<u32>::new();
+      v.try_reserve_exact(used)?;
+      v.extend_from_slice(&self.buf[..used]);
+      let result = LinuxFileHandle { v, mnt_id, at_flags: Some(AtFlags::empty()), unique_mnt_id: None };
+      #[cfg(feature = "verify-on-obtain")]
+      result.verify_reopen(d_fd, &self.path, AtFlags::empty())?;
+      Ok(result)
+   }
+}
+
+impl Default for HandleResolver
+{
+   fn default() -> HandleResolver
+   {
+      HandleResolver::new()
+   }
+}
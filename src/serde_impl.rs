@@ -0,0 +1,63 @@
+//! ```serde``` support, enabled by the ```serde``` feature
+use std::fmt;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use crate::LinuxFileHandle;
+use crate::ffi_bindings::*;
+
+/// Longest serialized handle: the header and the largest payload
+const MAX_WIRE_LEN: usize = 8 + MAX_HANDLE_SZ as usize;
+
+struct WireBytesVisitor;
+
+impl<'de> Visitor<'de> for WireBytesVisitor
+{
+   type Value = LinuxFileHandle;
+
+   fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result
+   {
+      formatter.write_str("the bytes of a file handle produced by to_wire_bytes()")
+   }
+
+   fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<LinuxFileHandle,E>
+   {
+      LinuxFileHandle::from_wire_bytes(v).map_err(E::custom)
+   }
+
+   /// Formats without a byte string type (like JSON) serialize the bytes as a sequence of numbers
+   fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<LinuxFileHandle,A::Error>
+   {
+      let mut bytes = Vec::<u8>::new();
+      bytes.try_reserve(seq.size_hint().unwrap_or(0).min(MAX_WIRE_LEN)).map_err(<A::Error as serde::de::Error>::custom)?;
+      while let Some(b) = seq.next_element::<u8>()?
+      {
+         if bytes.len() == MAX_WIRE_LEN { return Err(<A::Error as serde::de::Error>::invalid_length(bytes.len() + 1, &self)); }
+         bytes.try_reserve(1).map_err(<A::Error as serde::de::Error>::custom)?;
+         bytes.push(b);
+      }
+      self.visit_bytes(&bytes)
+   }
+}
+
+/// Serializes the bytes of ```to_wire_bytes()```: ```handle_type``` and the payload, in a layout independent of the byte order of the machine. The mount ID is left out, since it only has a meaning on the host (and until the next reboot)
+impl Serialize for LinuxFileHandle
+{
+   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok,S::Error>
+   {
+      let bytes = self.to_wire_bytes().map_err(<S::Error as serde::ser::Error>::custom)?;
+      serializer.serialize_bytes(&bytes)
+   }
+}
+
+/// Deserializes the bytes with ```from_wire_bytes()```, so corrupt data is an error rather than a handle with a wrong length. The handle has no mount ID
+impl<'de> Deserialize<'de> for LinuxFileHandle
+{
+   fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LinuxFileHandle,D::Error>
+   {
+      deserializer.deserialize_bytes(WireBytesVisitor)
+   }
+}
@@ -0,0 +1,40 @@
+//! Asynchronous wrappers running the system calls on the blocking thread pool of ```tokio```, enabled by the ```tokio``` feature
+use std::os::fd::AsFd;
+use std::os::fd::OwnedFd;
+use std::path::PathBuf;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+
+/// Runs ```f``` with ```spawn_blocking()```, turning a panic of the task into an error
+async fn run_blocking<T: Send + 'static>(f: impl FnOnce() -> std::io::Result<T> + Send + 'static) -> std::io::Result<T>
+{
+   match tokio::task::spawn_blocking(f).await
+   {
+      Ok(r) => r,
+      Err(e) => Err(std::io::Error::other(e)),
+   }
+}
+
+impl LinuxFileHandle
+{
+   /// Same as ```obtain()```, but runs on the blocking thread pool of ```tokio```, so that slow storage does not stall the runtime
+   ///
+   /// The directory file descriptor and the path are moved to the thread, and the descriptor is closed when the call is done
+   pub async fn obtain_async(dirfd: Option<OwnedFd>, path: PathBuf) -> std::io::Result<LinuxFileHandle>
+   {
+      run_blocking(move || LinuxFileHandle::obtain(dirfd.as_ref().map(|fd| fd.as_fd()), &path)).await
+   }
+
+   /// Same as ```open_by_handle()```, but runs on the blocking thread pool of ```tokio```
+   ///
+   /// A copy of the handle and ```mnt_fd``` are moved to the thread, and ```mnt_fd``` is closed when the call is done
+   ///
+   /// # Safety
+   ///
+   /// See ```open_by_handle()```
+   pub async unsafe fn open_by_handle_async(&self, mnt_fd: OwnedFd, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   {
+      let fh = self.duplicate()?;
+      run_blocking(move || unsafe { fh.open_by_handle(&mnt_fd, flags) }).await
+   }
+}
@@ -0,0 +1,205 @@
+//! Borrowed file handles, for callers that manage the memory of handles themselves
+use std::os::fd::AsRawFd;
+use std::os::fd::AsFd;
+#[cfg(feature = "bumpalo")]
+use std::os::fd::BorrowedFd;
+#[cfg(feature = "bumpalo")]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(feature = "bumpalo")]
+use std::path::Path;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::collections::TryReserveError;
+use crate::AtFlags;
+use crate::LinuxFileHandle;
+use crate::OpenFlags;
+#[cfg(feature = "bumpalo")]
+use crate::MAX_SIZING_ATTEMPTS;
+use crate::ffi_bindings::*;
+
+/// A file handle borrowing its words (in the same layout as ```LinuxFileHandle::get_slice()```) from memory owned by someone else, e.g. an arena
+#[derive(Clone, Copy)]
+pub struct HandleRef<'a>
+{
+   words: &'a [u32],
+   mnt_id: i32,
+   at_flags: Option<AtFlags>,
+}
+
+impl<'a> HandleRef<'a>
+{
+   /// Creates a view of custom words, in the layout of ```LinuxFileHandle::get_slice()```
+   pub fn new(words: &'a [u32]) -> HandleRef<'a>
+   {
+      HandleRef { words, mnt_id: -1, at_flags: None }
+   }
+
+   pub(crate) fn with_mnt_id(words: &'a [u32], mnt_id: i32) -> HandleRef<'a>
+   {
+      HandleRef { words, mnt_id, at_flags: None }
+   }
+
+   /// Retrieve the ```mnt_id``` value from ```name_to_handle_at()``` (will return None for views created from custom words)
+   pub fn get_mnt_id(&self) -> Option<i32>
+   {
+      if self.mnt_id >= 0 { Some(self.mnt_id) } else { None }
+   }
+
+   /// Same as ```LinuxFileHandle::get_at_flags()``` (will return None for views created from custom words)
+   pub fn get_at_flags(&self) -> Option<AtFlags>
+   {
+      self.at_flags
+   }
+
+   /// Same as ```LinuxFileHandle::is_openable()```
+   pub fn is_openable(&self) -> Option<bool>
+   {
+      self.at_flags.map(|f| !f.contains(AtFlags::AT_HANDLE_FID))
+   }
+
+   /// Access the words of file handle itself, in the same layout as ```LinuxFileHandle::get_slice()```
+   ///
+   /// The file handle should be considered an opaque value
+   pub fn get_slice(&self) -> &'a [u32]
+   {
+      self.words
+   }
+
+   /// Same as ```LinuxFileHandle::get_handle_type()```
+   pub fn get_handle_type(&self) -> u32
+   {
+      LinuxFileHandle::words_handle_type(self.words)
+   }
+
+   /// Same as ```LinuxFileHandle::handle_len()```
+   pub fn handle_len(&self) -> usize
+   {
+      LinuxFileHandle::words_handle_len(self.words)
+   }
+
+   /// Same as ```LinuxFileHandle::as_bytes()```, borrowing from the same memory as the view
+   pub fn as_bytes(&self) -> &'a [u8]
+   {
+      LinuxFileHandle::words_as_bytes(self.words)
+   }
+
+   /// Same as ```LinuxFileHandle::payload_bytes()```, borrowing from the same memory as the view
+   pub fn payload_bytes(&self) -> &'a [u8]
+   {
+      LinuxFileHandle::words_payload_bytes(self.words)
+   }
+
+   /// Same as ```LinuxFileHandle::len()```
+   pub fn len(&self) -> usize
+   {
+      self.payload_bytes().len()
+   }
+
+   /// Same as ```LinuxFileHandle::is_empty()```
+   pub fn is_empty(&self) -> bool
+   {
+      self.len() == 0
+   }
+
+   /// Same as ```LinuxFileHandle::get_vec()```
+   pub fn get_vec(&self) -> Result<Vec<u8>,TryReserveError>
+   {
+      LinuxFileHandle::words_to_vec(self.words)
+   }
+
+   /// Same as ```LinuxFileHandle::to_hex()```
+   pub fn to_hex(&self) -> String
+   {
+      LinuxFileHandle::words_to_hex(self.words)
+   }
+
+   /// Copies the handle into an owned ```LinuxFileHandle```, with the same mount ID and ```get_at_flags()``` (views do not keep the unique mount ID of ```obtain_with_unique_mnt_id()```)
+   pub fn to_owned_handle(&self) -> Result<LinuxFileHandle,TryReserveError>
+   {
+      let mut v = Vec::<u32>::new();
+      v.try_reserve(self.words.len())?;
+      v.extend_from_slice(self.words);
+      Ok(LinuxFileHandle { v, mnt_id: self.mnt_id, at_flags: self.at_flags, unique_mnt_id: None })
+   }
+
+   /// Same as ```LinuxFileHandle::open_by_handle()```, the kernel reads the handle from the borrowed words directly
+   ///
+   /// # Safety
+   ///
+   /// See ```LinuxFileHandle::open_by_handle()```
+   pub unsafe fn open_by_handle(&self, mnt_fd: impl AsFd, flags: OpenFlags) -> std::io::Result<OwnedFd>
+   {
+      flags.validate()?;
+      if !LinuxFileHandle::words_are_valid(self.words) { return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "structurally invalid file handle")); }
+      // open_by_handle_at() does not modify the handle, and only reads the header and handle_bytes of payload, which the words were checked to cover
+      let r = unsafe { open_by_handle_at(mnt_fd.as_fd().as_raw_fd(), self.words.as_ptr() as *const file_handle as *mut file_handle, LinuxFileHandle::get_signed(flags.bits())?) };
+      if r >= 0
+      {
+         unsafe { Ok(OwnedFd::from_raw_fd(r)) }
+      }
+      else
+      {
+         Err(LinuxFileHandle::open_error())
+      }
+   }
+}
+
+impl LinuxFileHandle
+{
+   /// Borrows the handle as a ```HandleRef```
+   pub fn as_handle_ref(&self) -> HandleRef<'_>
+   {
+      HandleRef { words: &self.v, mnt_id: self.mnt_id, at_flags: self.at_flags }
+   }
+
+   /// Same as ```obtain()```, but places the handle into ```arena``` and returns a view of it, so that no heap memory is allocated per handle
+   ///
+   /// The path is copied into the arena as well. The size of the handle is first probed with a header on the stack, then the handle is obtained directly into the arena with room for exactly that size (with the ```no-size-probe``` feature, the probe is skipped and room is made for ```MAX_HANDLE_SZ``` bytes). If the size changes between the calls, the words allocated for the smaller size remain in the arena until it is reset
+   #[cfg(feature = "bumpalo")]
+   pub fn obtain_in_arena<'a>(arena: &'a bumpalo::Bump, dirfd: Option<BorrowedFd<'_>>, path: impl AsRef<Path>) -> std::io::Result<HandleRef<'a>>
+   {
+      let path = path.as_ref().as_os_str().as_bytes();
+      let d_fd = match dirfd
+      {
+         Some(fd) => fd.as_raw_fd(),
+         None => AT_FDCWD,
+      };
+      Self::check_no_nul(path)?;
+      let path_v = arena.alloc_slice_fill_copy(path.len() + 1, 0u8);
+      path_v[..path.len()].copy_from_slice(path);
+      let mut mnt_id: i32 = 0;
+      let call = |buf: &mut [u32], mnt_id: &mut i32| {
+         if unsafe { name_to_handle_at(d_fd, path_v.as_ptr() as *const i8, buf.as_mut_ptr() as *mut file_handle, mnt_id as *mut i32, 0) } == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) }
+      };
+      let mut header: [u32; 2] = [0; 2];
+      if cfg!(feature = "no-size-probe")
+      {
+         header[0] = MAX_HANDLE_SZ;
+      }
+      else
+      {
+         match call(&mut header, &mut mnt_id)
+         {
+            Ok(()) => return Ok(HandleRef { words: arena.alloc_slice_copy(&header), mnt_id, at_flags: Some(AtFlags::empty()) }), // a handle without payload
+            Err(e) if e.raw_os_error() == Some(Self::get_signed(EOVERFLOW)?) => {},
+            Err(e) => return Err(e),
+         }
+      }
+      for _ in 0..MAX_SIZING_ATTEMPTS
+      {
+         let words = arena.alloc_slice_fill_copy(2 + Self::get_usize(header[0])?.div_ceil(4), 0u32);
+         words[0] = header[0];
+         match call(words, &mut mnt_id)
+         {
+            Ok(()) => {
+               let used = 2 + Self::get_usize(words[0])?.div_ceil(4);
+               let words: &'a [u32] = words;
+               return Ok(HandleRef { words: &words[..used], mnt_id, at_flags: Some(AtFlags::empty()) });
+            },
+            Err(e) if e.raw_os_error() == Some(Self::get_signed(EOVERFLOW)?) => header[0] = words[0],
+            Err(e) => return Err(e),
+         }
+      }
+      Err(std::io::Error::other("size of the file handle kept changing between calls to name_to_handle_at()"))
+   }
+}
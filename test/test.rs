@@ -3,10 +3,48 @@ use std::mem::MaybeUninit;
 use std::os::fd::AsRawFd;
 use name_to_handle_at_rs::LinuxFileHandle;
 use name_to_handle_at_rs::OpenFlags;
+use std::path::PathBuf;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
+
+    /// A directory under the temporary directory, removed with its contents when dropped, so that a test failing halfway through does not leave it behind
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+       fn new(name: &str) -> TempDir {
+          let path = std::env::temp_dir().join(format!("name_to_handle_at_rs-{}-{}", name, std::process::id()));
+          // left behind by a run that was killed
+          let _ = std::fs::remove_dir_all(&path);
+          std::fs::create_dir_all(&path).unwrap();
+          TempDir(path)
+       }
+    }
+
+    impl std::ops::Deref for TempDir {
+       type Target = Path;
+
+       fn deref(&self) -> &Path {
+          &self.0
+       }
+    }
+
+    impl AsRef<Path> for TempDir {
+       fn as_ref(&self) -> &Path {
+          &self.0
+       }
+    }
+
+    impl Drop for TempDir {
+       fn drop(&mut self) {
+          let _ = std::fs::remove_dir_all(&self.0);
+       }
+    }
 
     #[test]
     fn it_works() {
@@ -29,4 +67,1294 @@ mod tests {
        assert_eq!(unsafe { libc::fstat(owned_fd.as_raw_fd(), opened.as_mut_ptr()) }, 0);
        unsafe { assert_eq!(original.assume_init().st_ino, opened.assume_init().st_ino) };
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn archive_works() {
+       let entries = vec![
+          (PathBuf::from("/bin/sh"), LinuxFileHandle::obtain_follow(None, "/bin/sh").unwrap()),
+          (PathBuf::from("/"), LinuxFileHandle::obtain(None, "/").unwrap()),
+       ];
+       let mut archive = Vec::<u8>::new();
+       name_to_handle_at_rs::write_archive(&entries, &mut archive).unwrap();
+       let restored = name_to_handle_at_rs::read_archive(&mut archive.as_slice()).unwrap();
+       assert_eq!(restored.len(), entries.len());
+       for ((path, fh), (r_path, r_fh)) in entries.iter().zip(restored.iter())
+       {
+          assert_eq!(path, r_path);
+          assert_eq!(fh.get_slice(), r_fh.get_slice());
+       }
+       // an archive cut in the middle of an entry must be rejected
+       assert!(name_to_handle_at_rs::read_archive(&mut &archive[..archive.len() - 1]).is_err());
+    }
+    
+    #[test]
+    fn probe_works() {
+      // Like it_works, this test expects CAP_DAC_READ_SEARCH to be effective
+       assert_eq!(name_to_handle_at_rs::open_by_handle_available(), name_to_handle_at_rs::OpenAvailability::Available);
+       assert!(!name_to_handle_at_rs::open_requires_init_ns_cap());
+    }
+    
+    #[test]
+    fn absolute_path_policy_works() {
+       use name_to_handle_at_rs::AbsolutePathPolicy;
+       let dir = std::fs::File::open("/bin").unwrap();
+       let expected = LinuxFileHandle::obtain(Some(dir.as_fd()), "sh").unwrap();
+       let stripped = LinuxFileHandle::obtain_with_policy(Some(dir.as_fd()), "/sh", AbsolutePathPolicy::StripRoot).unwrap();
+       assert_eq!(expected.get_slice(), stripped.get_slice());
+       assert!(LinuxFileHandle::obtain_with_policy(Some(dir.as_fd()), "/bin/sh", AbsolutePathPolicy::Reject).is_err());
+       assert!(LinuxFileHandle::obtain_with_policy(Some(dir.as_fd()), "/bin/sh", AbsolutePathPolicy::Allow).is_ok());
+    }
+    
+    #[test]
+    fn raw_ptr_comparison_works() {
+       let fh = LinuxFileHandle::obtain_follow(None, "/bin/sh").unwrap();
+       let other = LinuxFileHandle::obtain(None, "/").unwrap();
+       let raw = fh.get_slice().as_ptr() as *const std::ffi::c_void;
+       assert!(unsafe { fh.matches_raw_ptr(raw) });
+       assert!(!unsafe { other.matches_raw_ptr(raw) });
+    }
+    
+    #[test]
+    fn mntns_works() {
+      // This test needs CAP_SYS_ADMIN to enter a mount namespace (its own in this case)
+       let ns = std::fs::File::open("/proc/self/ns/mnt").unwrap();
+       let fh = LinuxFileHandle::obtain_in_mntns(ns.as_fd(), "/bin/sh").unwrap();
+       let expected = LinuxFileHandle::obtain(None, "/bin/sh").unwrap();
+       assert_eq!(fh.get_slice(), expected.get_slice());
+       assert_eq!(fh.get_mnt_id(), expected.get_mnt_id());
+    }
+    
+    #[test]
+    fn debug_dump_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let dump = fh.debug_dump();
+       assert!(dump.starts_with(&format!("handle_bytes: {}\n", fh.get_slice()[0])));
+       assert!(dump.contains(&format!("mnt_id: {}\n", fh.get_mnt_id().unwrap())));
+    }
+    
+    #[test]
+    fn u64_words_work() {
+       let fh = LinuxFileHandle::obtain_follow(None, "/bin/sh").unwrap();
+       let words = fh.to_u64_words().unwrap();
+       let restored = LinuxFileHandle::from_u64_words(&words, fh.get_mnt_id()).unwrap();
+       // only the words covering handle_bytes survive the round trip
+       let words_needed = 2 + (fh.get_slice()[0] as usize).div_ceil(4);
+       assert_eq!(restored.get_slice()[..words_needed], fh.get_slice()[..words_needed]);
+       assert_eq!(restored.get_slice().len(), words_needed);
+       assert_eq!(restored.get_mnt_id(), fh.get_mnt_id());
+       use name_to_handle_at_rs::HandleError;
+       let header = |handle_bytes: u32| u64::from_ne_bytes([handle_bytes.to_ne_bytes(), 1u32.to_ne_bytes()].concat().try_into().unwrap());
+       assert!(matches!(LinuxFileHandle::from_u64_words(&[], None), Err(HandleError::Malformed(_))));
+       assert!(matches!(LinuxFileHandle::from_u64_words(&[header(9), 0], None), Err(HandleError::Malformed(_))));
+       assert!(matches!(LinuxFileHandle::from_u64_words(&[header(129); 18], None), Err(HandleError::HandleBytesTooLarge(129))));
+       assert_eq!(LinuxFileHandle::from_u64_words(&[header(8), 0], None).unwrap().get_slice(), [8, 1, 0, 0]);
+    }
+    
+    #[test]
+    fn inode_search_works() {
+       use std::os::unix::fs::MetadataExt;
+       let root = std::fs::File::open("/usr").unwrap();
+       let ino = std::fs::symlink_metadata("/usr/bin").unwrap().ino();
+       let fh = LinuxFileHandle::obtain_by_inode(root.as_fd(), ino, 1).unwrap().unwrap();
+       assert_eq!(fh.get_slice(), LinuxFileHandle::obtain(None, "/usr/bin").unwrap().get_slice());
+       assert!(LinuxFileHandle::obtain_by_inode(root.as_fd(), ino, 0).unwrap().is_none());
+    }
+    
+    #[test]
+    fn ephemeral_fs_works() {
+       let shm = std::fs::File::open("/dev/shm").unwrap(); // tmpfs on most systems
+       assert!(name_to_handle_at_rs::is_ephemeral_fs(shm.as_fd()).unwrap());
+       let proc_fs = std::fs::File::open("/proc").unwrap();
+       assert!(!name_to_handle_at_rs::is_ephemeral_fs(proc_fs.as_fd()).unwrap());
+    }
+    
+    #[test]
+    fn at_flags_are_recorded() {
+       use name_to_handle_at_rs::AtFlags;
+       let fh = LinuxFileHandle::obtain_follow(None, "/bin/sh").unwrap();
+       assert_eq!(fh.get_at_flags(), Some(AtFlags::AT_SYMLINK_FOLLOW));
+       let copy = LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap();
+       assert_eq!(copy.get_at_flags(), None);
+    }
+    
+    #[test]
+    fn nfsd_cache_line_works() {
+       use name_to_handle_at_rs::HandleError;
+       let fh = LinuxFileHandle::from_nfsd_cache_line("* 6 0x0a1b2c3d4e5f60718293a4b5c6d7e8f9 /export\n").unwrap();
+       assert_eq!(fh.get_slice(), [16, 6, 0x0a1b2c3d, 0x4e5f6071, 0x8293a4b5, 0xc6d7e8f9]);
+       assert_eq!(fh.get_mnt_id(), None);
+       let fh = LinuxFileHandle::from_nfsd_cache_line("* 1 0x00000001").unwrap();
+       assert_eq!(fh.get_slice(), [4, 1, 1]);
+       // the form written to the cache channel
+       let fh = LinuxFileHandle::from_nfsd_cache_line("* 1 \\x01000000").unwrap();
+       assert_eq!(fh.payload_bytes(), [1, 0, 0, 0]);
+       assert_eq!(LinuxFileHandle::from_nfsd_cache_line("#domain fsidtype fsid [path]").err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidInput));
+       assert_eq!(LinuxFileHandle::from_nfsd_cache_line("* 1 0x0008010").err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidData));
+       assert_eq!(LinuxFileHandle::from_nfsd_cache_line("* 1 0x+0000001").err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidData));
+       assert_eq!(LinuxFileHandle::from_nfsd_cache_line("* x 0x00000000").err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidData));
+       let oversized = format!("* 6 0x{}", "00000000".repeat(33));
+       let err = LinuxFileHandle::from_nfsd_cache_line(&oversized).unwrap_err();
+       assert!(matches!(err.get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::HandleBytesTooLarge(132))));
+    }
+    
+    #[test]
+    fn nlink_works() {
+       use std::os::unix::fs::MetadataExt;
+       let (fh, nlink) = LinuxFileHandle::obtain_with_nlink(None, "/usr").unwrap();
+       assert_eq!(fh.get_slice(), LinuxFileHandle::obtain(None, "/usr").unwrap().get_slice());
+       assert_eq!(nlink, std::fs::symlink_metadata("/usr").unwrap().nlink());
+    }
+    
+    #[test]
+    fn mount_table_works() {
+       let table = name_to_handle_at_rs::MountTable::load().unwrap();
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.resolve_mount_path(&table), Some(std::path::Path::new("/")));
+       assert!(name_to_handle_at_rs::MountTable::load_with_limit(0).is_err());
+       let mut refreshed = table.clone();
+       assert!(!refreshed.refresh().unwrap() || refreshed != table);
+    }
+    
+    #[test]
+    fn bind_root_works() {
+      // This test needs CAP_SYS_ADMIN to bind-mount a directory in a private mount namespace
+       let base = TempDir::new("bind");
+       let subtree = base.join("source").join("subtree");
+       let target = base.join("target");
+       std::fs::create_dir_all(&subtree).unwrap();
+       std::fs::create_dir_all(&target).unwrap();
+       let (sub_c, target_c) = (CString::new(subtree.as_os_str().as_bytes()).unwrap(), CString::new(target.as_os_str().as_bytes()).unwrap());
+       let (bind_root, original) = std::thread::spawn(move || {
+          // the mount namespace (and the bind mount with it) only exists while this thread is alive
+          assert_eq!(unsafe { libc::unshare(libc::CLONE_NEWNS) }, 0);
+          let root_c = CString::new("/").unwrap();
+          assert_eq!(unsafe { libc::mount(std::ptr::null(), root_c.as_ptr(), std::ptr::null(), libc::MS_REC | libc::MS_PRIVATE, std::ptr::null()) }, 0);
+          assert_eq!(unsafe { libc::mount(sub_c.as_ptr(), target_c.as_ptr(), std::ptr::null(), libc::MS_BIND, std::ptr::null()) }, 0);
+          let dir = std::fs::File::open(target_c.to_str().unwrap()).unwrap();
+          (LinuxFileHandle::obtain_bind_root(dir.as_fd()).unwrap(), LinuxFileHandle::obtain(None, sub_c.to_str().unwrap()).unwrap())
+       }).join().unwrap();
+       assert_eq!(bind_root.get_slice(), original.get_slice());
+       assert_ne!(bind_root.get_mnt_id(), original.get_mnt_id());
+    }
+    
+    #[test]
+    fn diff_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let copy = LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap();
+       assert_eq!(fh.diff(&copy), format!("mnt_id: {} != none\n", fh.get_mnt_id().unwrap()));
+       assert_eq!(copy.diff(&LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap()), "");
+    }
+    
+    #[test]
+    fn change_token_works() {
+       use name_to_handle_at_rs::ChangeToken;
+       let tmp_dir = TempDir::new("token");
+       let path = tmp_dir.join("file");
+       std::fs::write(&path, b"first").unwrap();
+       let path_str = path.to_str().unwrap();
+       let (fh, content) = LinuxFileHandle::obtain_with_content_hash(None, path_str, ChangeToken::Content).unwrap();
+       let (_, metadata) = LinuxFileHandle::obtain_with_content_hash(None, path_str, ChangeToken::Metadata).unwrap();
+       assert_eq!(fh.get_slice(), LinuxFileHandle::obtain(None, path_str).unwrap().get_slice());
+       assert_eq!(content, LinuxFileHandle::obtain_with_content_hash(None, path_str, ChangeToken::Content).unwrap().1);
+       std::fs::write(&path, b"second").unwrap();
+       assert_ne!(content, LinuxFileHandle::obtain_with_content_hash(None, path_str, ChangeToken::Content).unwrap().1);
+       assert_ne!(metadata, LinuxFileHandle::obtain_with_content_hash(None, path_str, ChangeToken::Metadata).unwrap().1);
+    }
+    
+    #[test]
+    fn outdated_encoding_works() {
+       use name_to_handle_at_rs::is_outdated_encoding;
+       let root = std::fs::File::open("/").unwrap();
+       // handle_bytes claims more payload than stored
+       let fh = LinuxFileHandle::try_from(&[8u32, 1, 0][..]).unwrap();
+       let err = unsafe { fh.open_by_handle(root.as_fd(), OpenFlags::O_PATH) }.err().unwrap();
+       assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+       assert!(!is_outdated_encoding(&err));
+       assert!(is_outdated_encoding(&std::io::Error::from_raw_os_error(22)));
+       assert!(!is_outdated_encoding(&std::io::Error::from_raw_os_error(116)));
+    }
+    
+    #[test]
+    fn handle_ref_works() {
+       let f_obj = std::fs::File::open("/bin/sh").unwrap();
+       let fh = LinuxFileHandle::obtain_fd(Some(f_obj.as_fd())).unwrap();
+       let view = fh.as_handle_ref();
+       assert_eq!(view.get_slice(), fh.get_slice());
+       assert_eq!(view.get_mnt_id(), fh.get_mnt_id());
+       assert_eq!(view.to_owned_handle().unwrap().get_slice(), fh.get_slice());
+       let owned_fd = unsafe { view.open_by_handle(f_obj.as_fd(), OpenFlags::O_PATH).unwrap() };
+       let mut original = MaybeUninit::<libc::stat>::uninit();
+       let mut opened = MaybeUninit::<libc::stat>::uninit();
+       assert_eq!(unsafe { libc::fstat(f_obj.as_raw_fd(), original.as_mut_ptr()) }, 0);
+       assert_eq!(unsafe { libc::fstat(owned_fd.as_raw_fd(), opened.as_mut_ptr()) }, 0);
+       unsafe { assert_eq!(original.assume_init().st_ino, opened.assume_init().st_ino) };
+    }
+    
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    fn arena_works() {
+       let arena = bumpalo::Bump::new();
+       let view = LinuxFileHandle::obtain_in_arena(&arena, None, "/").unwrap();
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let words_needed = 2 + (fh.get_slice()[0] as usize).div_ceil(4);
+       assert_eq!(view.get_slice(), &fh.get_slice()[..words_needed]);
+       assert_eq!(view.get_mnt_id(), fh.get_mnt_id());
+       assert_eq!(view.get_handle_type(), fh.get_handle_type());
+       assert_eq!(view.is_openable(), Some(true));
+       assert_eq!(view.to_owned_handle().unwrap().is_openable(), Some(true));
+    }
+    
+    #[test]
+    fn handle_ref_accessors_work() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let view = fh.as_handle_ref();
+       assert_eq!(view.get_handle_type(), fh.get_handle_type());
+       assert_eq!(view.handle_len(), fh.handle_len());
+       assert_eq!(view.as_bytes(), fh.as_bytes());
+       assert_eq!(view.payload_bytes(), fh.payload_bytes());
+       assert_eq!((view.len(), view.is_empty()), (fh.len(), fh.is_empty()));
+       assert_eq!(view.get_vec().unwrap(), fh.get_vec().unwrap());
+       assert_eq!(view.to_hex(), fh.to_hex());
+       assert_eq!(view.get_at_flags(), fh.get_at_flags());
+       assert_eq!(view.to_owned_handle().unwrap().get_at_flags(), fh.get_at_flags());
+       let custom = name_to_handle_at_rs::HandleRef::new(fh.get_slice());
+       assert_eq!(custom.is_openable(), None);
+       assert_eq!(custom.as_bytes(), fh.as_bytes());
+    }
+    
+    #[test]
+    fn array_works() {
+       use name_to_handle_at_rs::HandleError;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let (arr, len) = fh.to_array::<136>().unwrap();
+       assert_eq!(&arr[..len], fh.get_vec().unwrap().as_slice());
+       assert!(arr[len..].iter().all(|b| *b == 0));
+       assert_eq!(LinuxFileHandle::from_array(&arr, len).unwrap().get_slice(), fh.get_slice());
+       assert!(matches!(fh.to_array::<4>(), Err(HandleError::BufferTooSmall { available: 4, .. })));
+       assert!(matches!(LinuxFileHandle::from_array(&arr, 137), Err(HandleError::Malformed(_))));
+    }
+    
+    #[test]
+    fn fs_uuid_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       fh.fs_uuid().unwrap();
+       if let Ok(shm) = LinuxFileHandle::obtain(None, "/dev/shm") {
+          assert_eq!(shm.fs_uuid().unwrap(), None);
+       }
+       let raw = LinuxFileHandle::try_from(fh.get_slice()).unwrap();
+       assert_eq!(raw.fs_uuid().err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidInput));
+    }
+    
+    #[test]
+    fn initial_words_work() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let words_needed = 2 + (fh.get_slice()[0] as usize).div_ceil(4);
+       // deliberately oversized, the first call succeeds
+       let hinted = LinuxFileHandle::obtain_with_initial_words(None, "/", 32).unwrap();
+       assert_eq!(hinted.get_slice(), &fh.get_slice()[..words_needed]);
+       assert_eq!(hinted.get_vec().unwrap().len(), 8 + fh.get_slice()[0] as usize);
+       assert_eq!(hinted.get_mnt_id(), fh.get_mnt_id());
+       // larger than any handle, clamped to MAX_HANDLE_SZ
+       assert_eq!(LinuxFileHandle::obtain_with_initial_words(None, "/", 1000).unwrap().get_slice(), hinted.get_slice());
+       // undersized, the second call is made
+       let small = LinuxFileHandle::obtain_with_initial_words(None, "/", 1).unwrap();
+       assert_eq!(&small.get_slice()[..words_needed], hinted.get_slice());
+    }
+    
+    #[test]
+    fn dir_handle_iter_works() {
+       let dir = TempDir::new("dir");
+       for name in ["a", "b", "c"] {
+          std::fs::write(dir.join(name), name).unwrap();
+       }
+       let dir_obj = std::fs::File::open(&dir).unwrap();
+       let mut entries = name_to_handle_at_rs::dir_handle_iter(dir_obj.as_fd()).unwrap().map(|e| e.unwrap()).collect::<Vec<_>>();
+       entries.sort_by(|x, y| x.0.cmp(&y.0));
+       assert_eq!(entries.iter().map(|e| e.0.to_str().unwrap()).collect::<Vec<_>>(), ["a", "b", "c"]);
+       for (name, fh) in &entries {
+          let expected = LinuxFileHandle::obtain(Some(dir_obj.as_fd()), name.to_str().unwrap()).unwrap();
+          assert_eq!(fh.get_slice(), expected.get_slice());
+       }
+    }
+    
+    #[test]
+    fn described_record_works() {
+       let bin = std::fs::File::open("/bin").unwrap();
+       let fh = LinuxFileHandle::obtain_follow(Some(bin.as_fd()), "sh").unwrap();
+       let record = fh.to_described_record(Some(bin.as_fd()), "sh").unwrap();
+       let parsed = LinuxFileHandle::from_described_record(&record).unwrap();
+       assert_eq!(parsed.handle.get_slice(), fh.get_slice());
+       assert_eq!(parsed.handle.get_mnt_id(), fh.get_mnt_id());
+       assert_eq!(parsed.fs_uuid, fh.fs_uuid().unwrap());
+       assert_eq!(parsed.path, std::fs::canonicalize("/bin").unwrap().join("sh"));
+       assert!(LinuxFileHandle::from_described_record(&record[..record.len() - 1]).is_err());
+       let mut unknown_version = record.clone();
+       unknown_version[0] = 2;
+       assert!(LinuxFileHandle::from_described_record(&unknown_version).is_err());
+    }
+    
+    #[cfg(feature = "verify-on-obtain")]
+    #[test]
+    fn verify_on_obtain_works() {
+       // a directory, a regular file through AT_EMPTY_PATH and a device verified through its mount point
+       LinuxFileHandle::obtain(None, "/").unwrap();
+       LinuxFileHandle::obtain_fd(Some(std::fs::File::open("/bin/sh").unwrap().as_fd())).unwrap();
+       LinuxFileHandle::obtain(None, "/dev/null").unwrap();
+    }
+    
+    #[test]
+    fn opaque_token_works() {
+       // handle_type 1 with 4 bytes of payload
+       let fh = LinuxFileHandle::try_from(&[4u32, 1, u32::from_le_bytes([0xde, 0xad, 0xbe, 0xef]), 0][..]).unwrap();
+       assert_eq!(fh.identity_key().unwrap(), [1, 0, 0, 0, 0xde, 0xad, 0xbe, 0xef]);
+       assert_eq!(fh.opaque_token().unwrap(), "aeaaaag6vw7o6");
+       let root = LinuxFileHandle::obtain(None, "/").unwrap();
+       let token = root.opaque_token().unwrap();
+       assert!(token.bytes().all(|c| c.is_ascii_lowercase() || (b'2'..=b'7').contains(&c)));
+       assert_eq!(token, LinuxFileHandle::try_from(root.get_slice()).unwrap().opaque_token().unwrap());
+    }
+    
+    #[test]
+    fn dir_handle_resolver_works() {
+       use name_to_handle_at_rs::DirHandleResolver;
+       let bin = std::fs::File::open("/bin").unwrap();
+       let bin_handle = LinuxFileHandle::obtain_fd(Some(bin.as_fd())).unwrap();
+       let resolver = unsafe { DirHandleResolver::new(&bin_handle, bin.as_fd()) }.unwrap();
+       for name in ["sh", "ls"] {
+          assert_eq!(resolver.resolve(name).unwrap().get_slice(), LinuxFileHandle::obtain(Some(bin.as_fd()), name).unwrap().get_slice());
+       }
+       assert_eq!(resolver.resolve("a/b").err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidInput));
+       assert_eq!(resolver.resolve("").err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidInput));
+       let from_fd = DirHandleResolver::from_fd(bin.try_clone().unwrap().into());
+       assert_eq!(from_fd.resolve("sh").unwrap().get_slice(), resolver.resolve("sh").unwrap().get_slice());
+    }
+    
+    #[test]
+    fn retry_works() {
+       let fh = LinuxFileHandle::obtain_with_retry(None, "/", 3).unwrap();
+       assert_eq!(fh.get_slice(), LinuxFileHandle::obtain(None, "/").unwrap().get_slice());
+       // not transient, returned right away
+       let err = LinuxFileHandle::obtain_with_retry(None, "/nonexistent-name_to_handle_at_rs", 3).err().unwrap();
+       assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+    
+    #[test]
+    fn pack_many_works() {
+       use name_to_handle_at_rs::{pack_many, packed_size, unpack_many};
+       let handles = vec![
+          LinuxFileHandle::obtain(None, "/").unwrap(),
+          LinuxFileHandle::obtain_follow(None, "/bin/sh").unwrap(),
+       ];
+       let packed = pack_many(&handles).unwrap();
+       assert_eq!(packed.len(), packed_size(&handles));
+       let unpacked = unpack_many(&packed).unwrap();
+       assert_eq!(unpacked.len(), handles.len());
+       for (a, b) in unpacked.iter().zip(&handles) {
+          assert_eq!(a.get_slice(), b.get_slice());
+       }
+       assert_eq!(packed_size(&[]), 4);
+       assert!(unpack_many(&pack_many(&[]).unwrap()).unwrap().is_empty());
+       assert!(unpack_many(&packed[..packed.len() - 1]).is_err());
+       let mut trailing = packed.clone();
+       trailing.push(0);
+       assert!(unpack_many(&trailing).is_err());
+    }
+    
+    #[test]
+    fn generation_stable_works() {
+       let tmp_dir = TempDir::new("gen");
+       let path = tmp_dir.join("file");
+       std::fs::write(&path, b"gen").unwrap();
+       let f_obj = std::fs::File::open(&path).unwrap();
+       let fh = LinuxFileHandle::obtain_fd(Some(f_obj.as_fd())).unwrap();
+       let dump = fh.debug_dump();
+       match dump.lines().find_map(|l| l.strip_prefix("generation: ")) {
+          Some(generation) => {
+             let generation: u32 = generation.parse().unwrap();
+             assert!(unsafe { fh.generation_stable(f_obj.as_fd(), generation) }.unwrap());
+             assert!(!unsafe { fh.generation_stable(f_obj.as_fd(), generation.wrapping_add(1)) }.unwrap());
+          },
+          None => {
+             let err = unsafe { fh.generation_stable(f_obj.as_fd(), 0) }.err().unwrap();
+             assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+          },
+       }
+    }
+    
+    #[test]
+    fn obtain_builder_works() {
+       use name_to_handle_at_rs::AtFlags;
+       // compares the words covering handle_bytes only
+       let exact = |fh: &LinuxFileHandle| fh.get_slice()[..2 + (fh.get_slice()[0] as usize).div_ceil(4)].to_vec();
+       let bin = std::fs::File::open("/bin").unwrap();
+       let sh = std::fs::File::open("/bin/sh").unwrap();
+       let fh = LinuxFileHandle::obtain_builder().dir(bin.as_fd()).path("sh").flags(AtFlags::AT_SYMLINK_FOLLOW).initial_words(32).verify(sh.as_fd()).run().unwrap();
+       assert_eq!(exact(&fh), exact(&LinuxFileHandle::obtain_fd(Some(sh.as_fd())).unwrap()));
+       assert_eq!(fh.get_at_flags(), Some(AtFlags::AT_SYMLINK_FOLLOW));
+       let (fh, parent) = LinuxFileHandle::obtain_builder().path("/bin/sh").flags(AtFlags::AT_SYMLINK_FOLLOW).with_parent().run().unwrap();
+       assert_eq!(exact(&fh), exact(&LinuxFileHandle::obtain_follow(None, "/bin/sh").unwrap()));
+       assert_eq!(exact(&parent), exact(&LinuxFileHandle::obtain_follow(None, "/bin").unwrap()));
+       let (_, root_parent) = LinuxFileHandle::obtain_builder().path("/").with_parent().run().unwrap();
+       assert_eq!(exact(&root_parent), exact(&LinuxFileHandle::obtain(None, "/").unwrap()));
+       let own = LinuxFileHandle::obtain_builder().dir(bin.as_fd()).flags(AtFlags::AT_EMPTY_PATH).verify(bin.as_fd()).run().unwrap();
+       assert_eq!(exact(&own), exact(&LinuxFileHandle::obtain_fd(Some(bin.as_fd())).unwrap()));
+       assert!(LinuxFileHandle::obtain_builder().dir(bin.as_fd()).flags(AtFlags::AT_EMPTY_PATH).with_parent().run().is_err());
+    }
+    
+    #[test]
+    fn not_handleable_works() {
+       use name_to_handle_at_rs::{is_handleable_fd, HandleError};
+       let (sock, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+       let efd = unsafe { <std::os::fd::OwnedFd as std::os::fd::FromRawFd>::from_raw_fd(libc::eventfd(0, libc::EFD_CLOEXEC)) };
+       for fd in [sock.as_fd(), efd.as_fd()] {
+          assert!(!is_handleable_fd(fd).unwrap());
+          let err = LinuxFileHandle::obtain_fd(Some(fd)).err().unwrap();
+          assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+          assert!(matches!(err.get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::NotHandleable(_))));
+       }
+       assert!(is_handleable_fd(std::fs::File::open("/").unwrap().as_fd()).unwrap());
+    }
+    
+    #[test]
+    fn handle_map_works() {
+       use name_to_handle_at_rs::HandleMap;
+       let mut map = HandleMap::<u64>::new();
+       assert!(map.insert_obtained(1, None, "/").unwrap().is_none());
+       assert!(map.insert_obtained(2, None, "/bin").unwrap().is_none());
+       assert!(map.insert_obtained(1, None, "/").unwrap().is_some());
+       assert_eq!(map.len(), 2);
+       assert_eq!(map.get(&1).unwrap().get_slice(), LinuxFileHandle::obtain(None, "/").unwrap().get_slice());
+       let root = std::fs::File::open("/").unwrap();
+       let fd = unsafe { map.open(&1, root.as_fd(), OpenFlags::O_PATH) }.unwrap();
+       let mut original = MaybeUninit::<libc::stat>::uninit();
+       let mut opened = MaybeUninit::<libc::stat>::uninit();
+       assert_eq!(unsafe { libc::fstat(root.as_raw_fd(), original.as_mut_ptr()) }, 0);
+       assert_eq!(unsafe { libc::fstat(fd.as_raw_fd(), opened.as_mut_ptr()) }, 0);
+       unsafe { assert_eq!(original.assume_init().st_ino, opened.assume_init().st_ino) };
+       assert_eq!(unsafe { map.open(&3, root.as_fd(), OpenFlags::O_PATH) }.err().map(|e| e.kind()), Some(std::io::ErrorKind::NotFound));
+       assert!(map.remove(&2).is_some());
+       assert_eq!(map.len(), 1);
+    }
+    
+    #[test]
+    fn mnt_id_validation_works() {
+       use name_to_handle_at_rs::MountTable;
+       let table = MountTable::load().unwrap();
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert!(fh.mnt_id_still_valid(&table));
+       let entry = table.lookup(fh.get_mnt_id().unwrap()).unwrap().clone();
+       assert!(fh.mnt_id_still_matches(&table, &entry));
+       let mut replaced = entry.clone();
+       replaced.dev = (u32::MAX, u32::MAX);
+       assert!(!fh.mnt_id_still_matches(&table, &replaced));
+       let raw = LinuxFileHandle::try_from(fh.get_slice()).unwrap();
+       assert!(!raw.mnt_id_still_valid(&table));
+       assert!(!raw.mnt_id_still_matches(&table, &entry));
+       // a handle with the unique mount ID has no 32-bit one
+       let unique = LinuxFileHandle::obtain_with_unique_mnt_id(None, "/").unwrap();
+       if unique.get_unique_mnt_id().is_some() {
+          assert_eq!(unique.get_mnt_id(), None);
+          assert!(unique.mnt_id_still_valid(&table));
+          assert!(unique.mnt_id_still_matches(&table, &entry));
+          assert!(!unique.mnt_id_still_matches(&table, &replaced));
+       }
+    }
+    
+    #[test]
+    fn obtain_under_root_works() {
+       let root = TempDir::new("root");
+       std::fs::create_dir_all(root.join("etc")).unwrap();
+       std::fs::write(root.join("etc/config"), b"config").unwrap();
+       std::os::unix::fs::symlink("/etc/passwd", root.join("escape")).unwrap();
+       let root_str = root.to_str().unwrap();
+       let fh = LinuxFileHandle::obtain_under_root(root_str, "etc/config").unwrap();
+       let expected = LinuxFileHandle::obtain(None, root.join("etc/config").to_str().unwrap()).unwrap();
+       assert_eq!(fh.identity_key().unwrap(), expected.identity_key().unwrap());
+       for rel in ["../etc/passwd", "/etc/passwd", "escape/x"] {
+          let err = LinuxFileHandle::obtain_under_root(root_str, rel).err().unwrap();
+          assert_eq!(err.raw_os_error(), Some(libc::EXDEV));
+       }
+       // the trailing symbolic link itself is beneath the root
+       LinuxFileHandle::obtain_under_root(root_str, "escape").unwrap();
+    }
+    
+    #[test]
+    fn frame_truncation_works() {
+       use name_to_handle_at_rs::HandleError;
+       let handles = [LinuxFileHandle::obtain(None, "/").unwrap(), LinuxFileHandle::obtain(None, "/bin").unwrap()];
+       let mut stream = Vec::<u8>::new();
+       for fh in &handles {
+          fh.write_to(&mut stream).unwrap();
+       }
+       let read = LinuxFileHandle::iter_frames_from(stream.as_slice()).collect::<Result<Vec<_>, _>>().unwrap();
+       assert_eq!(read.len(), 2);
+       assert_eq!(read[1].get_slice(), handles[1].get_slice());
+       assert!(LinuxFileHandle::read_from(&mut &stream[..0]).unwrap().is_none());
+       // cut inside the length prefix and inside the payload of the second frame
+       let first_len = stream.len() - (4 + handles[1].get_slice().len() * 4);
+       for cut in [first_len + 2, stream.len() - 1] {
+          let mut frames = LinuxFileHandle::iter_frames_from(&stream[..cut]);
+          assert!(frames.next().unwrap().is_ok());
+          assert!(matches!(frames.next(), Some(Err(HandleError::Truncated))));
+          assert!(frames.next().is_none());
+       }
+    }
+    
+    #[test]
+    fn self_exe_works() {
+       let fh = LinuxFileHandle::obtain_self_exe().unwrap();
+       let exe = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+       assert_eq!(fh.identity_key().unwrap(), LinuxFileHandle::obtain_fd(Some(exe.as_fd())).unwrap().identity_key().unwrap());
+    }
+    
+    #[test]
+    fn interner_works() {
+       use name_to_handle_at_rs::HandleInterner;
+       let interner = HandleInterner::new();
+       let a = interner.intern(&LinuxFileHandle::obtain(None, "/").unwrap());
+       let b = interner.intern(&LinuxFileHandle::obtain_with_initial_words(None, "/", 32).unwrap());
+       let c = interner.intern(&LinuxFileHandle::obtain(None, "/bin").unwrap());
+       assert!(a.shares_storage(&b));
+       assert!(!a.shares_storage(&c));
+       assert_eq!(interner.len(), 2);
+       assert_eq!(a.view().get_mnt_id(), LinuxFileHandle::obtain(None, "/").unwrap().get_mnt_id());
+       let root = std::fs::File::open("/").unwrap();
+       unsafe { a.open_by_handle(root.as_fd(), OpenFlags::O_PATH) }.unwrap();
+       drop(c);
+       interner.purge();
+       assert_eq!(interner.len(), 1);
+       // the byte past handle_bytes in the last word is padding
+       let padded = LinuxFileHandle::try_from(&[3u32, 1, u32::from_ne_bytes([0xaa, 0xbb, 0xcc, 0xdd])][..]).unwrap();
+       let unpadded = LinuxFileHandle::try_from(&[3u32, 1, u32::from_ne_bytes([0xaa, 0xbb, 0xcc, 0])][..]).unwrap();
+       assert_eq!(padded, unpadded);
+       assert!(interner.intern(&padded).shares_storage(&interner.intern(&unpadded)));
+       assert_eq!(interner.len(), 2);
+    }
+    
+    #[test]
+    fn obtain_if_accessible_works() {
+       use name_to_handle_at_rs::AccessMode;
+       let fh = LinuxFileHandle::obtain_if_accessible(None, "/", AccessMode::R_OK | AccessMode::X_OK).unwrap().unwrap();
+       assert_eq!(fh.get_slice(), LinuxFileHandle::obtain(None, "/").unwrap().get_slice());
+       assert!(LinuxFileHandle::obtain_if_accessible(None, "/", AccessMode::empty()).unwrap().is_some());
+       // nobody may execute a file without execute bits, not even root
+       let tmp_dir = TempDir::new("access");
+       let path = tmp_dir.join("file");
+       std::fs::write(&path, b"data").unwrap();
+       assert!(LinuxFileHandle::obtain_if_accessible(None, path.to_str().unwrap(), AccessMode::X_OK).unwrap().is_none());
+       std::fs::remove_file(&path).unwrap();
+       let err = LinuxFileHandle::obtain_if_accessible(None, "/nonexistent-name_to_handle_at_rs", AccessMode::R_OK).err().unwrap();
+       assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+    
+    #[test]
+    fn distinct_mounts_works() {
+       use name_to_handle_at_rs::distinct_mounts;
+       let root = LinuxFileHandle::obtain(None, "/").unwrap();
+       let handles = vec![
+          LinuxFileHandle::obtain(None, "/").unwrap(),
+          LinuxFileHandle::obtain(None, "/bin").unwrap(),
+          LinuxFileHandle::obtain(None, "/dev/shm").unwrap(),
+          LinuxFileHandle::try_from(root.get_slice()).unwrap(),
+       ];
+       let mut expected = vec![root.get_mnt_id().unwrap(), handles[2].get_mnt_id().unwrap()];
+       expected.sort_unstable();
+       expected.dedup();
+       assert_eq!(distinct_mounts(&handles), expected);
+       assert!(distinct_mounts(&[]).is_empty());
+    }
+    
+    #[test]
+    fn eq_hash_debug_work() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       // different padding and no mount ID
+       let hinted = LinuxFileHandle::obtain_with_initial_words(None, "/", 32).unwrap();
+       let raw = LinuxFileHandle::try_from(hinted.get_slice()).unwrap();
+       assert_eq!(fh, hinted);
+       assert_eq!(fh, raw);
+       assert_ne!(fh, LinuxFileHandle::obtain(None, "/bin").unwrap());
+       let set: std::collections::HashSet<LinuxFileHandle> = [fh.clone(), hinted, raw].into_iter().collect();
+       assert_eq!(set.len(), 1);
+       let small = LinuxFileHandle::try_from(&[4u32, 1, u32::from_le_bytes([0x2a, 0x1b, 0x3c, 0x4d]), 0][..]).unwrap();
+       assert_eq!(format!("{:?}", small), "LinuxFileHandle { mnt_id: none, handle_bytes: 4, bytes: 04000000010000002a1b3c4d }");
+       assert!(format!("{:?}", fh).starts_with(&format!("LinuxFileHandle {{ mnt_id: {}, ", fh.get_mnt_id().unwrap())));
+    }
+    
+    #[test]
+    fn handle_type_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.get_handle_type(), fh.get_slice()[1]);
+       assert_eq!(LinuxFileHandle::try_from(&[4u32, 0x81, 0][..]).unwrap().get_handle_type(), 0x81);
+       assert_eq!(LinuxFileHandle::try_from(&[0u32][..]).unwrap().get_handle_type(), 0);
+    }
+    
+    #[test]
+    fn alignment_works() {
+       for handle_bytes in [0usize, 1, 4, 5, 8] {
+          // with one extra padding word that must be dropped
+          let mut words = vec![handle_bytes as u32, 1];
+          words.extend(std::iter::repeat_n(0xffffffffu32, handle_bytes.div_ceil(4)));
+          words.push(0);
+          let padded = LinuxFileHandle::try_from(words.as_slice()).unwrap();
+          let restored = LinuxFileHandle::from_u64_words(&padded.to_u64_words().unwrap(), None).unwrap();
+          assert_eq!(restored.get_slice(), &words[..2 + handle_bytes.div_ceil(4)]);
+          let mut bytes = vec![0xffu8; 8 + handle_bytes];
+          bytes[..4].copy_from_slice(&(handle_bytes as u32).to_ne_bytes());
+          assert_eq!(LinuxFileHandle::from_vec(&bytes).unwrap().get_slice().len(), 2 + handle_bytes.div_ceil(4));
+       }
+    }
+
+    #[test]
+    fn get_vec_has_no_padding() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let handle_bytes = fh.get_slice()[0] as usize;
+       let bytes = fh.get_vec().unwrap();
+       assert_eq!(bytes.len(), 8 + handle_bytes);
+       assert_eq!(LinuxFileHandle::from_vec(&bytes).unwrap(), fh);
+    }
+
+    #[test]
+    fn non_utf8_paths_work() {
+       let dir = TempDir::new("non-utf8");
+       let file = dir.join(std::ffi::OsStr::from_bytes(b"caf\xe9"));
+       std::fs::write(&file, b"").unwrap();
+       let fh = LinuxFileHandle::obtain(None, &file).unwrap();
+       let by_fd = LinuxFileHandle::obtain_fd(Some(std::fs::File::open(&file).unwrap().as_fd())).unwrap();
+       assert_eq!(fh, by_fd);
+       assert_eq!(LinuxFileHandle::obtain_follow(None, &file).unwrap(), fh);
+       assert_eq!(LinuxFileHandle::obtain(None, std::ffi::OsStr::from_bytes(b"/tmp\0/")).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn interior_nul_is_rejected() {
+       let dir = TempDir::new("nul");
+       std::fs::write(dir.join("foo"), b"").unwrap();
+       let dir_f = std::fs::File::open(&dir).unwrap();
+       assert!(LinuxFileHandle::obtain(Some(dir_f.as_fd()), "foo").is_ok());
+       for r in [LinuxFileHandle::obtain(Some(dir_f.as_fd()), "foo\0bar"), LinuxFileHandle::obtain_follow(Some(dir_f.as_fd()), "foo\0bar"), LinuxFileHandle::obtain_with_initial_words(Some(dir_f.as_fd()), "foo\0bar", 8)] {
+          assert_eq!(r.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+       }
+    }
+
+    #[test]
+    fn tmpfile_mode_works() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let dir = TempDir::new("tmpfile");
+       let fh = LinuxFileHandle::obtain(None, &dir).unwrap();
+       let mnt = std::fs::File::open(&dir).unwrap();
+       let mode_of = |f: std::fs::File| f.metadata().unwrap().permissions().mode() & 0o7777;
+       let fd = unsafe { fh.open_by_handle_with_mode(mnt.as_fd(), OpenFlags::O_TMPFILE | OpenFlags::O_RDWR, 0o640).unwrap() };
+       assert_eq!(mode_of(std::fs::File::from(fd)), 0o640);
+       let fd = unsafe { fh.open_by_handle(mnt.as_fd(), OpenFlags::O_TMPFILE | OpenFlags::O_RDWR).unwrap() };
+       assert_eq!(mode_of(std::fs::File::from(fd)), 0);
+       // ignored without O_TMPFILE
+       let fd = unsafe { fh.open_by_handle_with_mode(mnt.as_fd(), OpenFlags::O_RDONLY, 0o600).unwrap() };
+       assert_eq!(mode_of(std::fs::File::from(fd)), mode_of(std::fs::File::open(&dir).unwrap()));
+       // and with O_CREAT, since the file of a handle already exists
+       let file = dir.join("existing");
+       std::fs::write(&file, b"").unwrap();
+       std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+       let file_fh = LinuxFileHandle::obtain(None, &file).unwrap();
+       let fd = unsafe { file_fh.open_by_handle_with_mode(mnt.as_fd(), OpenFlags::O_CREAT | OpenFlags::O_RDONLY, 0o600).unwrap() };
+       assert_eq!(mode_of(std::fs::File::from(fd)), 0o644);
+    }
+
+    #[test]
+    fn wire_bytes_work() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let handle_bytes = fh.get_slice()[0];
+       let wire = fh.to_wire_bytes().unwrap();
+       assert_eq!(wire.len(), 8 + handle_bytes as usize);
+       assert_eq!(&wire[..4], &handle_bytes.to_be_bytes());
+       assert_eq!(&wire[4..8], &fh.get_handle_type().to_be_bytes());
+       assert_eq!(&wire[8..], &fh.get_vec().unwrap()[8..]);
+       let restored = LinuxFileHandle::from_wire_bytes(&wire).unwrap();
+       assert_eq!(restored, fh);
+       assert_eq!(restored.get_mnt_id(), None);
+       // an odd payload length is preserved byte for byte
+       let odd = LinuxFileHandle::from_wire_bytes(&[0, 0, 0, 3, 0, 0, 0, 1, 0xaa, 0xbb, 0xcc]).unwrap();
+       assert_eq!(odd.to_wire_bytes().unwrap(), [0, 0, 0, 3, 0, 0, 0, 1, 0xaa, 0xbb, 0xcc]);
+       assert!(LinuxFileHandle::from_wire_bytes(&wire[..7]).is_err());
+       assert!(LinuxFileHandle::from_wire_bytes(&wire[..wire.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn as_bytes_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.as_bytes(), fh.get_vec().unwrap().as_slice());
+       let words = fh.get_slice();
+       let mut expected = Vec::new();
+       for w in words {
+          expected.extend_from_slice(&w.to_ne_bytes());
+       }
+       assert_eq!(fh.as_bytes(), &expected[..8 + words[0] as usize]);
+       // a view of the words, not a copy
+       assert_eq!(fh.as_bytes().as_ptr(), words.as_ptr() as *const u8);
+       // the padding of the last word is not included
+       let mut src = Vec::new();
+       src.extend_from_slice(&3u32.to_ne_bytes());
+       src.extend_from_slice(&1u32.to_ne_bytes());
+       src.extend_from_slice(&[0xaa, 0xbb, 0xcc]);
+       assert_eq!(LinuxFileHandle::from_vec(&src).unwrap().as_bytes(), src.as_slice());
+    }
+
+    #[test]
+    fn from_vec_rejects_malformed() {
+       assert!(LinuxFileHandle::from_vec(&[]).is_err());
+       assert!(LinuxFileHandle::from_vec(&4u32.to_ne_bytes()).is_err());
+       let mut src = Vec::new();
+       src.extend_from_slice(&16u32.to_ne_bytes());
+       src.extend_from_slice(&1u32.to_ne_bytes());
+       src.extend_from_slice(&[0; 12]);
+       assert_eq!(LinuxFileHandle::from_vec(&src).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+       src.extend_from_slice(&[0; 4]);
+       assert!(LinuxFileHandle::from_vec(&src).is_ok());
+    }
+
+    #[test]
+    fn resolve_path_works() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let dir = TempDir::new("resolve");
+       let dir = dir.canonicalize().unwrap();
+       std::fs::write(dir.join("a"), b"").unwrap();
+       let fh = LinuxFileHandle::obtain(None, dir.join("a")).unwrap();
+       let mnt = std::fs::File::open(&dir).unwrap();
+       assert_eq!(unsafe { fh.resolve_path(mnt.as_fd()) }.unwrap(), dir.join("a"));
+       std::fs::rename(dir.join("a"), dir.join("b")).unwrap();
+       assert_eq!(unsafe { fh.resolve_path(mnt.as_fd()) }.unwrap(), dir.join("b"));
+    }
+
+    #[test]
+    fn mount_fd_for_id_works() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       use std::os::unix::fs::MetadataExt;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let mnt = fh.open_mount_fd().unwrap();
+       let fd = unsafe { fh.open_by_handle(mnt.as_fd(), OpenFlags::O_PATH).unwrap() };
+       let expected = std::fs::metadata("/").unwrap();
+       let actual = std::fs::File::from(fd).metadata().unwrap();
+       assert_eq!((actual.dev(), actual.ino()), (expected.dev(), expected.ino()));
+       assert_eq!(name_to_handle_at_rs::mount_fd_for_id(-1).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+       assert_eq!(LinuxFileHandle::try_from(fh.get_slice()).unwrap().open_mount_fd().unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_works() {
+       use serde::Deserialize;
+       use serde::de::value::BytesDeserializer;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let wire = fh.to_wire_bytes().unwrap();
+       let restored = LinuxFileHandle::deserialize(BytesDeserializer::<serde::de::value::Error>::new(&wire)).unwrap();
+       assert_eq!(restored, fh);
+       assert_eq!(restored.get_mnt_id(), None);
+       assert!(LinuxFileHandle::deserialize(BytesDeserializer::<serde::de::value::Error>::new(&wire[..wire.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn byte_conversions_work() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let bytes: Vec<u8> = fh.duplicate().unwrap().try_into().unwrap();
+       assert_eq!(bytes, fh.get_vec().unwrap());
+       let restored: LinuxFileHandle = bytes.as_slice().try_into().unwrap();
+       assert_eq!(restored, fh);
+       assert!(LinuxFileHandle::try_from(&bytes[..4]).is_err());
+       let mut out = Vec::new();
+       std::io::Write::write_all(&mut out, fh.as_ref()).unwrap();
+       assert_eq!(out, bytes);
+    }
+
+    #[test]
+    fn conversion_errors_are_distinguishable() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let f_obj = std::fs::File::open("/").unwrap();
+       let err = unsafe { fh.open_by_handle(f_obj.as_fd(), OpenFlags::from_bits_retain(1 << 31)).unwrap_err() };
+       assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+       assert!(err.raw_os_error().is_none());
+       assert!(matches!(err.get_ref().and_then(|e| e.downcast_ref::<name_to_handle_at_rs::HandleError>()), Some(name_to_handle_at_rs::HandleError::Conversion)));
+    }
+
+    #[test]
+    fn from_raw_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let bytes = fh.get_vec().unwrap();
+       assert_eq!(LinuxFileHandle::from_raw(fh.get_handle_type(), &bytes[8..]).unwrap(), fh);
+       assert!(LinuxFileHandle::from_raw(1, &[0; 129]).is_err());
+       // an unaligned copy, like a handle inline in an event buffer
+       let mut buf = vec![0u8; 1];
+       buf.extend_from_slice(&bytes);
+       let copy = unsafe { LinuxFileHandle::from_file_handle_ptr(buf[1..].as_ptr() as *const std::ffi::c_void).unwrap() };
+       assert_eq!(copy, fh);
+       assert_eq!(copy.get_mnt_id(), None);
+       let huge = [u32::MAX, 1];
+       assert!(unsafe { LinuxFileHandle::from_file_handle_ptr(huge.as_ptr() as *const std::ffi::c_void) }.is_err());
+    }
+
+    #[test]
+    fn unique_mnt_id_works() {
+       let fh = LinuxFileHandle::obtain_with_unique_mnt_id(None, "/").unwrap();
+       assert_eq!(fh, LinuxFileHandle::obtain(None, "/").unwrap());
+       match fh.get_unique_mnt_id() {
+          Some(id) => {
+             assert_eq!(fh.get_mnt_id(), None);
+             // STATX_MNT_ID_UNIQUE
+             let mut stx = MaybeUninit::<libc::statx>::uninit();
+             assert_eq!(unsafe { libc::statx(libc::AT_FDCWD, c"/".as_ptr(), 0, 0x4000, stx.as_mut_ptr()) }, 0);
+             assert_eq!(unsafe { stx.assume_init() }.stx_mnt_id, id);
+          },
+          None => assert!(fh.get_mnt_id().is_some()), // kernel older than 6.12
+       }
+       assert_eq!(LinuxFileHandle::obtain(None, "/").unwrap().get_unique_mnt_id(), None);
+    }
+
+    #[test]
+    fn open_by_handle_checked_works() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       use std::os::unix::fs::MetadataExt;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let f_obj = std::fs::File::open("/").unwrap();
+       let dev = f_obj.metadata().unwrap().dev();
+       assert!(unsafe { fh.open_by_handle_checked(f_obj.as_fd(), OpenFlags::O_PATH, &[dev]) }.is_ok());
+       let err = unsafe { fh.open_by_handle_checked(f_obj.as_fd(), OpenFlags::O_PATH, &[dev + 1]) }.unwrap_err();
+       assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+       assert!(unsafe { fh.open_by_handle_checked(f_obj.as_fd(), OpenFlags::O_PATH, &[]) }.is_err());
+    }
+
+    #[test]
+    fn handle_resolver_works() {
+       let mut resolver = name_to_handle_at_rs::HandleResolver::new();
+       for path in ["/", "/tmp", "/dev/shm", "/"] {
+          let fh = resolver.resolve(None, path).unwrap();
+          let expected = LinuxFileHandle::obtain(None, path).unwrap();
+          assert_eq!(fh.get_slice(), expected.get_slice());
+          assert_eq!(fh.get_mnt_id(), expected.get_mnt_id());
+       }
+       assert_eq!(resolver.resolve(None, "/nonexistent/path").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+       assert_eq!(resolver.resolve(None, "/tmp\0/x").unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn obtain_fast_works() {
+       for path in ["/", "/tmp", "/dev/shm"] {
+          let fh = LinuxFileHandle::obtain_fast(None, path).unwrap();
+          let expected = LinuxFileHandle::obtain(None, path).unwrap();
+          assert_eq!(fh.get_slice(), expected.get_slice());
+          assert!(fh.get_slice()[0] <= name_to_handle_at_rs::MAX_HANDLE_SZ);
+       }
+    }
+
+    #[test]
+    fn hex_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let hex = fh.to_hex();
+       assert_eq!(hex.len(), 2 * fh.get_vec().unwrap().len());
+       assert_eq!(fh.to_string(), hex);
+       assert_eq!(LinuxFileHandle::from_hex(&hex).unwrap(), fh);
+       assert_eq!(LinuxFileHandle::from_hex(&hex.to_uppercase()).unwrap(), fh);
+       assert!(LinuxFileHandle::from_hex(&hex[1..]).is_err());
+       assert!(LinuxFileHandle::from_hex(&format!("zz{}", &hex[2..])).is_err());
+       assert!(LinuxFileHandle::from_hex("").is_err());
+       assert!(LinuxFileHandle::from_hex("ff").is_err());
+    }
+
+    #[test]
+    fn display_leaves_out_padding() {
+       // 3 bytes of payload and a padding word past them
+       let padded = LinuxFileHandle::try_from([3u32, 1, u32::from_ne_bytes([0xaa, 0xbb, 0xcc, 0xdd]), 0xffffffff].as_slice()).unwrap();
+       let expected: String = 3u32.to_ne_bytes().iter().chain(&1u32.to_ne_bytes()).chain(&[0xaa, 0xbb, 0xcc]).map(|b| format!("{:02x}", b)).collect();
+       assert_eq!(padded.to_string(), expected);
+       assert_eq!(LinuxFileHandle::from_hex(&padded.to_string()).unwrap(), padded);
+    }
+
+    #[test]
+    fn open_accepts_any_as_fd() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let f_obj = std::fs::File::open("/").unwrap();
+       let by_file = unsafe { fh.open_by_handle(&f_obj, OpenFlags::O_PATH).unwrap() };
+       let owned: std::os::fd::OwnedFd = f_obj.try_clone().unwrap().into();
+       let by_owned = unsafe { fh.open_by_handle(&owned, OpenFlags::O_PATH).unwrap() };
+       let by_borrowed = unsafe { fh.open_by_handle(f_obj.as_fd(), OpenFlags::O_PATH).unwrap() };
+       for fd in [by_file, by_owned, by_borrowed] {
+          assert_eq!(LinuxFileHandle::obtain_fd(Some(fd.as_fd())).unwrap(), fh);
+       }
+       assert_eq!(unsafe { fh.resolve_path(&f_obj) }.unwrap(), std::path::Path::new("/"));
+    }
+
+    #[test]
+    fn same_file_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert!(fh.same_file(&LinuxFileHandle::obtain_follow(None, "/").unwrap()));
+       assert!(!fh.same_file(&LinuxFileHandle::obtain(None, "/tmp").unwrap()));
+       // same bytes, but no mount ID
+       let copy = LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap();
+       assert_eq!(copy, fh);
+       assert!(!fh.same_file(&copy));
+       assert!(!copy.same_file(&copy));
+       let unique = LinuxFileHandle::obtain_with_unique_mnt_id(None, "/").unwrap();
+       assert!(unique.same_file(&LinuxFileHandle::obtain_with_unique_mnt_id(None, "/").unwrap()));
+    }
+
+    #[test]
+    fn obtain_full_works() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let (fh, mnt) = LinuxFileHandle::obtain_full(None, "/tmp").unwrap();
+       assert_eq!(fh, LinuxFileHandle::obtain(None, "/tmp").unwrap());
+       let fd = unsafe { fh.open_by_handle(&mnt, OpenFlags::O_PATH).unwrap() };
+       assert_eq!(LinuxFileHandle::obtain_fd(Some(fd.as_fd())).unwrap(), fh);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_works() {
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+       let fh = rt.block_on(LinuxFileHandle::obtain_async(None, PathBuf::from("/"))).unwrap();
+       assert_eq!(fh, LinuxFileHandle::obtain(None, "/").unwrap());
+       let dir: std::os::fd::OwnedFd = std::fs::File::open("/").unwrap().into();
+       let rel = rt.block_on(LinuxFileHandle::obtain_async(Some(dir.try_clone().unwrap()), PathBuf::from("tmp"))).unwrap();
+       assert_eq!(rel, LinuxFileHandle::obtain(None, "/tmp").unwrap());
+       let fd = rt.block_on(unsafe { fh.open_by_handle_async(dir, OpenFlags::O_PATH) }).unwrap();
+       assert_eq!(LinuxFileHandle::obtain_fd(Some(fd.as_fd())).unwrap(), fh);
+    }
+
+    #[test]
+    fn open_flags_are_validated() {
+       use name_to_handle_at_rs::HandleError;
+       assert!((OpenFlags::O_RDONLY | OpenFlags::O_CLOEXEC).validate().is_ok());
+       assert!((OpenFlags::O_TMPFILE | OpenFlags::O_RDWR).validate().is_ok());
+       assert!(matches!((OpenFlags::O_WRONLY | OpenFlags::O_RDWR).validate(), Err(HandleError::InvalidFlags(_))));
+       assert!(matches!(OpenFlags::O_TMPFILE.validate(), Err(HandleError::InvalidFlags(_))));
+       let no_dir = OpenFlags::from_bits_retain((OpenFlags::O_TMPFILE | OpenFlags::O_WRONLY).bits() & !OpenFlags::O_DIRECTORY.bits());
+       assert!(matches!(no_dir.validate(), Err(HandleError::InvalidFlags(_))));
+       assert!((OpenFlags::O_CREAT | OpenFlags::O_RDWR).validate().is_ok());
+       assert!(matches!((OpenFlags::O_CREAT | OpenFlags::O_EXCL | OpenFlags::O_RDWR).validate(), Err(HandleError::InvalidFlags(_))));
+       // rejected before the system call, so this does not need CAP_DAC_READ_SEARCH
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let root = std::fs::File::open("/").unwrap();
+       let err = unsafe { fh.open_by_handle(root.as_fd(), OpenFlags::O_WRONLY | OpenFlags::O_RDWR) }.unwrap_err();
+       assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+       assert!(matches!(err.get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::InvalidFlags(_))));
+    }
+
+    #[test]
+    fn fid_works() {
+       use name_to_handle_at_rs::HandleError;
+       let tmp_dir = TempDir::new("fid");
+       let tmp = tmp_dir.join("file");
+       std::fs::write(&tmp, b"fid").unwrap();
+       match LinuxFileHandle::obtain_fid(None, &tmp)
+       {
+          Ok(fh) => {
+             assert!(fh.as_bytes().len() > 8);
+             assert_eq!(fh.get_mnt_id(), LinuxFileHandle::obtain(None, &tmp).unwrap().get_mnt_id());
+             assert_eq!(fh, LinuxFileHandle::obtain_fid(None, &tmp).unwrap());
+          },
+          // kernels older than 6.5
+          Err(e) => assert!(matches!(e.get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::FlagUnsupported(_)))),
+       }
+    }
+
+    #[test]
+    fn parts_round_trip() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let payload = fh.payload_bytes();
+       assert_eq!(payload.len(), fh.get_slice()[0] as usize);
+       assert_eq!(payload, &fh.as_bytes()[8..]);
+       let back = LinuxFileHandle::from_parts(fh.get_handle_type(), payload).unwrap();
+       assert_eq!(back, fh);
+       assert_eq!(back.payload_bytes(), payload);
+    }
+
+    #[test]
+    fn vec_round_trip_is_lossless() {
+       let mut seed: u32 = 0x9e3779b9;
+       for len in 1..=128usize {
+          let payload: Vec<u8> = (0..len).map(|_| { seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8 }).collect();
+          let fh = LinuxFileHandle::from_raw(seed, &payload).unwrap();
+          let bytes = fh.get_vec().unwrap();
+          assert_eq!(bytes.len(), 8 + len);
+          let back = LinuxFileHandle::from_vec(&bytes).unwrap();
+          assert_eq!(back.get_slice(), fh.get_slice(), "handle_bytes {}", len);
+          assert_eq!(back.get_vec().unwrap(), bytes);
+          // with the padding of older versions of get_vec()
+          let mut padded = bytes.clone();
+          padded.resize(8 + len.div_ceil(4) * 4 + 4, 0xff);
+          assert_eq!(LinuxFileHandle::from_vec(&padded).unwrap().get_slice(), fh.get_slice());
+       }
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap().get_slice(), fh.get_slice());
+    }
+
+    #[test]
+    fn open_capability_is_reported() {
+       use name_to_handle_at_rs::{has_open_capability, HandleError};
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let root = std::fs::File::open("/").unwrap();
+       let r = unsafe { fh.open_by_handle(root.as_fd(), OpenFlags::O_PATH) };
+       if has_open_capability() {
+          // a seccomp filter may still deny it, but never as a missing capability
+          if let Err(e) = r { assert!(!matches!(e.get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::MissingCapability(_)))); }
+       } else {
+          let e = r.unwrap_err();
+          assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+          assert!(matches!(e.get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::MissingCapability(_))));
+       }
+    }
+
+    #[test]
+    fn parts_with_mnt_work() {
+       use name_to_handle_at_rs::HandleError;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       let mnt_id = fh.get_mnt_id().unwrap();
+       let back = LinuxFileHandle::from_parts_with_mnt(mnt_id, fh.get_handle_type(), fh.payload_bytes()).unwrap();
+       assert_eq!(back, fh);
+       assert_eq!(back.get_mnt_id(), Some(mnt_id));
+       assert!(matches!(LinuxFileHandle::from_parts_with_mnt(-1, 1, &[0; 8]), Err(HandleError::Malformed(_))));
+       assert!(matches!(LinuxFileHandle::from_parts_with_mnt(mnt_id, 1, &[0; 129]), Err(HandleError::HandleBytesTooLarge(129))));
+    }
+
+    #[test]
+    fn handle_len_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.handle_len(), fh.get_vec().unwrap().len());
+       assert_eq!(fh.handle_len(), 8 + fh.payload_bytes().len());
+       for len in [0, 1, 4, 5, 128] {
+          assert_eq!(LinuxFileHandle::from_raw(1, &vec![7; len]).unwrap().handle_len(), 8 + len);
+       }
+    }
+
+    #[test]
+    fn clone_matches_duplicate() {
+       let fh = LinuxFileHandle::obtain_with_unique_mnt_id(None, "/").unwrap();
+       let (cloned, duplicated) = (fh.clone(), fh.duplicate().unwrap());
+       for copy in [&cloned, &duplicated] {
+          assert_eq!(copy.get_slice(), fh.get_slice());
+          assert_eq!(copy.get_mnt_id(), fh.get_mnt_id());
+          assert_eq!(copy.get_unique_mnt_id(), fh.get_unique_mnt_id());
+          assert_eq!(copy.get_at_flags(), fh.get_at_flags());
+       }
+    }
+
+    #[test]
+    fn same_filesystem_works() {
+       let root = LinuxFileHandle::obtain(None, "/").unwrap();
+       let bin = LinuxFileHandle::obtain(None, "/bin").unwrap();
+       assert_eq!(root.same_filesystem(&bin), Some(root.get_mnt_id() == bin.get_mnt_id()));
+       assert_eq!(root.same_filesystem(&root.duplicate().unwrap()), Some(true));
+       let proc = LinuxFileHandle::obtain(None, "/proc/self/fd");
+       if let Ok(proc) = proc {
+          assert_eq!(root.same_filesystem(&proc), Some(false));
+       }
+       let unknown = LinuxFileHandle::from_vec(&root.get_vec().unwrap()).unwrap();
+       assert_eq!(root.same_filesystem(&unknown), None);
+       assert_eq!(unknown.same_filesystem(&unknown), None);
+    }
+
+    #[test]
+    fn obtain_with_flags_works() {
+       use name_to_handle_at_rs::AtFlags;
+       let dir = TempDir::new("flags");
+       std::fs::write(dir.join("target"), b"flags").unwrap();
+       std::os::unix::fs::symlink("target", dir.join("link")).unwrap();
+       let dir_f = std::fs::File::open(&dir).unwrap();
+       let target = LinuxFileHandle::obtain(Some(dir_f.as_fd()), "target").unwrap();
+       let link = LinuxFileHandle::obtain(Some(dir_f.as_fd()), "link").unwrap();
+       assert_ne!(target, link);
+       assert_eq!(LinuxFileHandle::obtain_with_flags(Some(dir_f.as_fd()), "link", AtFlags::empty()).unwrap(), link);
+       assert_eq!(LinuxFileHandle::obtain_with_flags(Some(dir_f.as_fd()), "link", AtFlags::AT_SYMLINK_FOLLOW).unwrap(), target);
+       // an empty path with AT_EMPTY_PATH is the descriptor itself, whether or not AT_SYMLINK_FOLLOW is set
+       use std::os::unix::fs::OpenOptionsExt;
+       let link_fd = std::fs::OpenOptions::new().read(true).custom_flags((OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW).bits() as i32).open(dir.join("link")).unwrap();
+       let both = LinuxFileHandle::obtain_with_flags(Some(link_fd.as_fd()), "", AtFlags::AT_EMPTY_PATH | AtFlags::AT_SYMLINK_FOLLOW).unwrap();
+       assert_eq!(both, link);
+       assert_eq!(both.get_at_flags(), Some(AtFlags::AT_EMPTY_PATH | AtFlags::AT_SYMLINK_FOLLOW));
+    }
+
+    #[test]
+    fn huge_handle_bytes_are_rejected() {
+       use name_to_handle_at_rs::HandleError;
+       let mut src = Vec::new();
+       src.extend_from_slice(&0xffffffffu32.to_ne_bytes());
+       src.extend_from_slice(&1u32.to_ne_bytes());
+       src.extend_from_slice(&[0; 16]);
+       assert!(matches!(LinuxFileHandle::from_vec(&src).unwrap_err().get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::HandleBytesTooLarge(0xffffffff))));
+       // even if the payload really is that long
+       let mut long = Vec::new();
+       long.extend_from_slice(&129u32.to_ne_bytes());
+       long.extend_from_slice(&1u32.to_ne_bytes());
+       long.extend_from_slice(&[0; 129]);
+       assert!(matches!(LinuxFileHandle::from_vec(&long).unwrap_err().get_ref().and_then(|e| e.downcast_ref::<HandleError>()), Some(HandleError::HandleBytesTooLarge(129))));
+       assert!(matches!(LinuxFileHandle::from_raw(1, &[0; 129]), Err(HandleError::HandleBytesTooLarge(129))));
+       let huge = [u32::MAX, 1];
+       assert!(matches!(unsafe { LinuxFileHandle::from_file_handle_ptr(huge.as_ptr() as *const std::ffi::c_void) }, Err(HandleError::HandleBytesTooLarge(_))));
+       let mut wire = Vec::new();
+       wire.extend_from_slice(&0xffffffffu32.to_be_bytes());
+       wire.extend_from_slice(&1u32.to_be_bytes());
+       assert!(matches!(LinuxFileHandle::from_wire_bytes(&wire), Err(HandleError::HandleBytesTooLarge(_))));
+       let err: std::io::Error = HandleError::HandleBytesTooLarge(129).into();
+       assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn bytes_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.bytes().len(), fh.handle_len());
+       assert_eq!(fh.bytes().collect::<Vec<u8>>(), fh.get_vec().unwrap());
+       let odd = LinuxFileHandle::from_raw(1, &[1, 2, 3, 4, 5]).unwrap();
+       assert_eq!(odd.bytes().count(), 8 + 5);
+       assert!(odd.bytes().skip(8).eq([1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn try_from_owned_vec_works() {
+       use name_to_handle_at_rs::HandleError;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(LinuxFileHandle::try_from(fh.get_vec().unwrap()).unwrap().get_slice(), fh.get_slice());
+       assert!(matches!(LinuxFileHandle::try_from(vec![0u8; 4]), Err(HandleError::Malformed(_))));
+    }
+
+    #[test]
+    fn is_openable_works() {
+       use name_to_handle_at_rs::AtFlags;
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.is_openable(), Some(true));
+       assert_eq!(LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap().is_openable(), None);
+       if let Ok(fid) = LinuxFileHandle::obtain_fid(None, "/") {
+          assert_eq!(fid.is_openable(), Some(false));
+          assert_eq!(fid.get_at_flags(), Some(AtFlags::AT_HANDLE_FID));
+          assert_eq!(fid.duplicate().unwrap().is_openable(), Some(false));
+       }
+       if let Ok(fid) = LinuxFileHandle::obtain_with_flags(None, "/", AtFlags::AT_HANDLE_FID) {
+          assert_eq!(fid.is_openable(), Some(false));
+       }
+    }
+
+    #[test]
+    fn matches_fd_works() {
+       let root = std::fs::File::open("/").unwrap();
+       let bin = std::fs::File::open("/bin/sh").unwrap();
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert!(fh.matches_fd(&root).unwrap());
+       assert!(!fh.matches_fd(&bin).unwrap());
+       // without a mount ID, equal bytes are not enough
+       let unknown = LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap();
+       assert!(!unknown.matches_fd(&root).unwrap());
+       let restored = LinuxFileHandle::from_parts_with_mnt(fh.get_mnt_id().unwrap(), fh.get_handle_type(), fh.payload_bytes()).unwrap();
+       assert!(restored.matches_fd(&root).unwrap());
+       let other_mount = LinuxFileHandle::from_parts_with_mnt(fh.get_mnt_id().unwrap() + 1, fh.get_handle_type(), fh.payload_bytes()).unwrap();
+       assert!(!other_mount.matches_fd(&root).unwrap());
+    }
+
+    #[test]
+    fn obtain_fd_bound_works() {
+       let root = std::fs::File::open("/").unwrap();
+       let bound = LinuxFileHandle::obtain_fd_bound(root.as_fd()).unwrap();
+       assert_eq!(*bound.handle(), LinuxFileHandle::obtain(None, "/").unwrap());
+       assert_eq!(bound.mount_fd().as_raw_fd(), root.as_raw_fd());
+       // This part will fail if CAP_DAC_READ_SEARCH is not effective for the test
+       let fd = unsafe { bound.open(OpenFlags::O_PATH).unwrap() };
+       assert!(bound.handle().matches_fd(&fd).unwrap());
+       let fh = bound.into_handle();
+       assert_eq!(fh.get_mnt_id(), LinuxFileHandle::obtain(None, "/").unwrap().get_mnt_id());
+    }
+
+    #[test]
+    fn is_stale_works() {
+       use name_to_handle_at_rs::{is_outdated_encoding, is_stale};
+       let tmp_dir = TempDir::new("stale");
+       let tmp = tmp_dir.join("file");
+       std::fs::write(&tmp, b"stale").unwrap();
+       let fh = LinuxFileHandle::obtain(None, &tmp).unwrap();
+       let mnt = std::fs::File::open(&tmp_dir).unwrap();
+       std::fs::remove_file(&tmp).unwrap();
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let err = unsafe { fh.open_by_handle(mnt.as_fd(), OpenFlags::O_PATH) }.unwrap_err();
+       assert!(is_stale(&err));
+       assert!(!is_outdated_encoding(&err));
+       assert!(!is_stale(&std::io::Error::from(std::io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn ct_eq_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert!(fh.ct_eq(&fh.duplicate().unwrap()));
+       assert!(fh.ct_eq(&LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap()));
+       assert!(!fh.ct_eq(&LinuxFileHandle::obtain(None, "/bin/sh").unwrap()));
+       let (a, b) = (LinuxFileHandle::from_raw(1, &[1, 2, 3, 4, 5]).unwrap(), LinuxFileHandle::from_raw(1, &[1, 2, 3, 4, 6]).unwrap());
+       assert!(!a.ct_eq(&b));
+       assert!(!a.ct_eq(&LinuxFileHandle::from_raw(2, &[1, 2, 3, 4, 5]).unwrap()));
+       assert!(!a.ct_eq(&LinuxFileHandle::from_raw(1, &[1, 2, 3, 4]).unwrap()));
+    }
+
+    #[test]
+    fn symlink_under_o_path_dir_is_not_followed() {
+       use std::os::unix::fs::OpenOptionsExt;
+       let dir = TempDir::new("nofollow");
+       std::fs::write(dir.join("target"), b"nofollow").unwrap();
+       std::os::unix::fs::symlink("target", dir.join("link")).unwrap();
+       let dir_fd = std::fs::OpenOptions::new().read(true).custom_flags((OpenFlags::O_PATH | OpenFlags::O_DIRECTORY).bits() as i32).open(&dir).unwrap();
+       let link = LinuxFileHandle::obtain(Some(dir_fd.as_fd()), "link").unwrap();
+       let target = LinuxFileHandle::obtain(Some(dir_fd.as_fd()), "target").unwrap();
+       assert_ne!(link, target);
+       let link_fd = std::fs::OpenOptions::new().read(true).custom_flags((OpenFlags::O_PATH | OpenFlags::O_NOFOLLOW).bits() as i32).open(dir.join("link")).unwrap();
+       assert_eq!(link, LinuxFileHandle::obtain_fd(Some(link_fd.as_fd())).unwrap());
+       assert_eq!(LinuxFileHandle::obtain_follow(Some(dir_fd.as_fd()), "link").unwrap(), target);
+    }
+
+    #[test]
+    fn mount_fd_cache_works() {
+       use name_to_handle_at_rs::{is_stale, MountFdCache};
+       let mut cache = MountFdCache::new();
+       assert!(cache.is_empty());
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       // This test will fail if CAP_DAC_READ_SEARCH is not effective for it
+       let fd = unsafe { fh.open_with_cache(&mut cache, OpenFlags::O_PATH) }.unwrap();
+       assert!(fh.matches_fd(&fd).unwrap());
+       let cached = cache.get(fh.get_mnt_id().unwrap()).unwrap().as_raw_fd();
+       unsafe { LinuxFileHandle::obtain(None, "/bin").unwrap().open_with_cache(&mut cache, OpenFlags::O_PATH) }.unwrap();
+       assert_eq!(cache.get(fh.get_mnt_id().unwrap()).unwrap().as_raw_fd(), cached);
+       let tmp_dir = TempDir::new("fdcache");
+       let tmp = tmp_dir.join("file");
+       std::fs::write(&tmp, b"cache").unwrap();
+       let gone = LinuxFileHandle::obtain(None, &tmp).unwrap();
+       std::fs::remove_file(&tmp).unwrap();
+       let tmp_cached = cache.get(gone.get_mnt_id().unwrap()).unwrap().as_raw_fd();
+       assert!(is_stale(&unsafe { gone.open_with_cache(&mut cache, OpenFlags::O_PATH) }.unwrap_err()));
+       // a stale handle does not reopen the mount
+       assert_eq!(cache.get(gone.get_mnt_id().unwrap()).unwrap().as_raw_fd(), tmp_cached);
+       let unknown = LinuxFileHandle::from_vec(&fh.get_vec().unwrap()).unwrap();
+       assert_eq!(unsafe { unknown.open_with_cache(&mut cache, OpenFlags::O_PATH) }.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+       cache.clear();
+       assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn len_works() {
+       let fh = LinuxFileHandle::obtain(None, "/").unwrap();
+       assert_eq!(fh.len(), fh.get_slice()[0] as usize);
+       assert_eq!(fh.len() + 8, fh.handle_len());
+       assert!(!fh.is_empty());
+       let odd = LinuxFileHandle::from_raw(1, &[1, 2, 3]).unwrap();
+       assert_eq!(odd.len(), 3);
+       let empty = LinuxFileHandle::from_raw(1, &[]).unwrap();
+       assert_eq!(empty.len(), 0);
+       assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn path_arguments_accept_non_utf8() {
+       use name_to_handle_at_rs::DirHandleResolver;
+       let dir = TempDir::new("path-args");
+       let name = std::ffi::OsStr::from_bytes(b"\xff\xfe");
+       let path = dir.join(name);
+       std::fs::write(&path, b"x").unwrap();
+       let expected = LinuxFileHandle::obtain(None, &path).unwrap();
+       let dir_fd = std::fs::File::open(&dir).unwrap();
+       assert_eq!(LinuxFileHandle::obtain_with_retry(Some(dir_fd.as_fd()), name, 1).unwrap().get_slice(), expected.get_slice());
+       assert_eq!(LinuxFileHandle::obtain_sized_info(None, &path).unwrap().0.get_slice(), expected.get_slice());
+       assert_eq!(LinuxFileHandle::obtain_builder().path(&path).run().unwrap().get_slice(), expected.get_slice());
+       let resolver = DirHandleResolver::from_fd(dir_fd.try_clone().unwrap().into());
+       assert_eq!(resolver.resolve(name).unwrap().get_slice(), expected.get_slice());
+    }
+}
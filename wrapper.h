@@ -6,3 +6,11 @@
 #include <sys/stat.h>
 #include <fcntl.h>
 #include <errno.h>
+#include <sched.h>
+#include <sys/statfs.h>
+#include <linux/magic.h>
+#include <poll.h>
+#include <dirent.h>
+#include <linux/openat2.h>
+#include <sys/syscall.h>
+#include <unistd.h>